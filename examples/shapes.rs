@@ -1,10 +1,10 @@
-use kurbo::{Affine, BezPath, Ellipse, Size};
+use kurbo::{Affine, BezPath, Ellipse};
 use peniko::Color;
 #[cfg(feature = "pdf")]
 use selvage::Pdf;
 #[cfg(feature = "svg")]
 use selvage::Svg;
-use selvage::{PaintOp, SceneWhisperer, StaticShape};
+use selvage::{Bounds, DynSceneWhisperer, PaintOp, SceneWhisperer, StaticShape, StrokeAlignment};
 #[cfg(feature = "vello")]
 use vello::Scene;
 
@@ -13,10 +13,6 @@ mod utils;
 #[cfg(feature = "vello")]
 use utils::Renderer;
 
-const RENDER_SIZE: Size = Size {
-    width: 64.0,
-    height: 64.0,
-};
 const BG_COLOR: Color = peniko::Color::LIGHT_GRAY;
 const PEN_COLOR: Color = peniko::Color::BLACK;
 const STROKE_WIDTH: f64 = 1.0;
@@ -24,6 +20,7 @@ const OUTPUT_NAME: &str = "out";
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Symbol {
+    #[serde(with = "selvage::compact")]
     pub shape: StaticShape,
     pub paint_ops: Vec<PaintOp>,
     pub transform: kurbo::Affine,
@@ -42,6 +39,7 @@ fn serialized_shape() -> anyhow::Result<String> {
     let stroke = PaintOp::Stroke {
         style: kurbo::Stroke::new(STROKE_WIDTH).with_caps(kurbo::Cap::Butt),
         brush: peniko::Brush::Solid(PEN_COLOR),
+        alignment: StrokeAlignment::Center,
     };
     let sym = Symbol {
         shape: StaticShape::BezPath(tri),
@@ -63,38 +61,40 @@ fn main() -> anyhow::Result<()> {
     let s = serialized_shape()?;
     let shapes: Vec<Symbol> = serde_json::from_str(&s)?;
 
+    let mut bounds = Bounds::new(0.1);
+    for shape in &shapes {
+        bounds.apply_paint_ops(
+            shape.paint_ops.iter().map(|x| x.into()),
+            shape.transform,
+            shape.brush_transform,
+            &shape.shape,
+        );
+    }
+    let size = bounds.size_from_origin();
+    let render_size = kurbo::Size::new(size.width.ceil(), size.height.ceil());
+
     #[cfg(not(any(feature = "svg", feature = "vello", feature = "pdf")))]
     eprintln!("Must enable feature vello, svg, or pdf to do anything");
 
     #[cfg(feature = "vello")]
     let mut scene = Scene::new();
     #[cfg(feature = "svg")]
-    let mut svg = Svg::new(RENDER_SIZE);
+    let mut svg = Svg::new(render_size);
     #[cfg(feature = "pdf")]
-    let mut pdf = Pdf::new(RENDER_SIZE, 0.1);
+    let mut pdf = Pdf::new(render_size, 0.1);
 
-    for shape in shapes {
+    {
+        #[allow(unused_mut)]
+        let mut backends: Vec<Box<dyn DynSceneWhisperer>> = Vec::new();
         #[cfg(feature = "vello")]
-        {
-            scene.apply_paint_ops(
-                shape.paint_ops.iter().map(|x| x.into()),
-                shape.transform,
-                shape.brush_transform,
-                &shape.shape,
-            );
-        }
+        backends.push(Box::new(&mut scene));
         #[cfg(feature = "svg")]
-        {
-            svg.apply_paint_ops(
-                shape.paint_ops.iter().map(|x| x.into()),
-                shape.transform,
-                shape.brush_transform,
-                &shape.shape,
-            );
-        }
+        backends.push(Box::new(&mut svg));
         #[cfg(feature = "pdf")]
-        {
-            pdf.apply_paint_ops(
+        backends.push(Box::new(&mut pdf));
+
+        for shape in &shapes {
+            backends.apply_paint_ops(
                 shape.paint_ops.iter().map(|x| x.into()),
                 shape.transform,
                 shape.brush_transform,
@@ -118,7 +118,7 @@ fn main() -> anyhow::Result<()> {
     }
     #[cfg(feature = "vello")]
     {
-        let mut r = pollster::block_on(Renderer::new(RENDER_SIZE))?;
+        let mut r = pollster::block_on(Renderer::new(render_size))?;
         let mut target = r.texture("foo");
         let mut path_buf = std::path::PathBuf::from(OUTPUT_NAME);
         path_buf.set_extension("png");
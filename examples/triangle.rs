@@ -5,7 +5,7 @@ use peniko::Color;
 use selvage::Pdf;
 #[cfg(feature = "svg")]
 use selvage::Svg;
-use selvage::{PaintOp, SceneWhisperer, StaticShape};
+use selvage::{PaintOp, SceneWhisperer, StaticShape, StrokeAlignment};
 #[cfg(feature = "vello")]
 use vello::Scene;
 
@@ -41,6 +41,7 @@ fn serialized_shape() -> anyhow::Result<String> {
     let stroke = PaintOp::Stroke {
         style: kurbo::Stroke::new(STROKE_WIDTH).with_caps(kurbo::Cap::Butt),
         brush: peniko::Brush::Solid(PEN_COLOR),
+        alignment: StrokeAlignment::Center,
     };
     let sym = Symbol {
         // Static shape isn't really utilized here,
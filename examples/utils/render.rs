@@ -142,6 +142,8 @@ impl Renderer {
         let mut encoder = png::Encoder::new(&mut file, self.extents.width, self.extents.height);
         encoder.set_color(png::ColorType::Rgba);
         encoder.set_depth(png::BitDepth::Eight);
+        // The pixels are sRGB encoded, tagging them lets viewers color manage the image.
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
         let mut writer = encoder.write_header()?;
         writer.write_image_data(&result_unpadded)?;
         writer.finish()?;
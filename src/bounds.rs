@@ -0,0 +1,264 @@
+use crate::offset::{offset_path, StrokeAlignment};
+use crate::whisperer::PaintOpRef;
+use crate::SceneWhisperer;
+use kurbo::{Affine, BezPath, Rect, Shape, Size, Stroke, StrokeOpts};
+
+/// Measures the area painted by the ops applied to it.
+///
+/// Strokes are measured from their outline, so widths, joins, miter limits, caps and
+/// dashes are all accounted for. Ops inside of layers are limited to the layer clips.
+///
+/// ```
+/// # use selvage::{Bounds, PaintOpRef, SceneWhisperer};
+/// # use kurbo::{Affine, Circle, Rect};
+/// let mut bounds = Bounds::new(1e-3);
+/// let fill = PaintOpRef::Fill {
+///     style: peniko::Fill::NonZero,
+///     brush: (&peniko::Color::BLACK).into(),
+/// };
+/// bounds.apply_paint_op(fill, Affine::translate((5.0, 0.0)), None, &Circle::new((0.0, 0.0), 2.0));
+/// assert_eq!(bounds.bounds(), Some(Rect::new(3.0, -2.0, 7.0, 2.0)));
+/// ```
+pub struct Bounds {
+    tolerance: f64,
+    /// The clip of each layer which hasn't been popped, intersected with the enclosing ones.
+    layers: Vec<Rect>,
+    bounds: Option<Rect>,
+}
+
+impl Bounds {
+    pub fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            layers: Vec::new(),
+            bounds: None,
+        }
+    }
+
+    /// The union of everything painted so far, `None` if nothing was.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    /// The size needed to show everything painted so far without moving it,
+    /// i.e. with the origin at the top left.
+    pub fn size_from_origin(&self) -> Size {
+        self.bounds
+            .map_or(Size::ZERO, |b| Size::new(b.x1.max(0.0), b.y1.max(0.0)))
+    }
+
+    /// A transform which moves everything painted so far to the top left of `Size`,
+    /// with `margin` around it, along with that size. This is what a viewBox does.
+    pub fn fit(&self, margin: f64) -> (Affine, Size) {
+        match self.bounds {
+            Some(b) => (
+                Affine::translate((margin - b.x0, margin - b.y0)),
+                Size::new(b.width() + 2.0 * margin, b.height() + 2.0 * margin),
+            ),
+            None => (Affine::IDENTITY, Size::new(2.0 * margin, 2.0 * margin)),
+        }
+    }
+
+    fn path_bounds(path: &BezPath) -> Option<Rect> {
+        (!path.elements().is_empty()).then(|| path.bounding_box())
+    }
+
+    /// The area painted by `op`, before clipping.
+    fn paint_bounds(
+        &self,
+        op: &PaintOpRef<'_, '_>,
+        transform: Affine,
+        shape: &impl Shape,
+    ) -> Option<Rect> {
+        let tolerance = self.tolerance;
+        match op {
+            PaintOpRef::Fill { .. } => Self::path_bounds(&(transform * shape.to_path(tolerance))),
+            PaintOpRef::Stroke {
+                style, alignment, ..
+            } => {
+                let outline = stroke_outline(shape, style, *alignment, tolerance);
+                let painted = Self::path_bounds(&(transform * outline))?;
+                match alignment {
+                    // Inside strokes are clipped to the shape.
+                    StrokeAlignment::Inside => {
+                        let inside = Self::path_bounds(&(transform * shape.to_path(tolerance)))?;
+                        Some(painted.intersect(inside))
+                    }
+                    _ => Some(painted),
+                }
+            }
+            PaintOpRef::PushLayer { .. } | PaintOpRef::PopLayer => None,
+        }
+    }
+
+    fn measure(&mut self, op: &PaintOpRef<'_, '_>, transform: Affine, shape: &impl Shape) {
+        match op {
+            PaintOpRef::PushLayer { .. } => {
+                let clip = Self::path_bounds(&(transform * shape.to_path(self.tolerance)))
+                    .unwrap_or(Rect::ZERO);
+                let clip = match self.layers.last() {
+                    Some(outer) => outer.intersect(clip),
+                    None => clip,
+                };
+                self.layers.push(clip);
+            }
+            PaintOpRef::PopLayer => {
+                self.layers.pop();
+            }
+            PaintOpRef::Fill { .. } | PaintOpRef::Stroke { .. } => {
+                let Some(mut painted) = self.paint_bounds(op, transform, shape) else {
+                    return;
+                };
+                if let Some(clip) = self.layers.last() {
+                    painted = painted.intersect(*clip);
+                    if painted.area() == 0.0 {
+                        return;
+                    }
+                }
+                self.bounds = Some(match self.bounds {
+                    Some(bounds) => bounds.union(painted),
+                    None => painted,
+                });
+            }
+        }
+    }
+}
+
+/// The outline of a stroke, before clipping inside aligned strokes to the shape.
+///
+/// Inside strokes are twice as wide, like the backends paint them before clipping.
+pub(crate) fn stroke_outline(
+    shape: &impl Shape,
+    style: &Stroke,
+    alignment: StrokeAlignment,
+    tolerance: f64,
+) -> BezPath {
+    let opts = StrokeOpts::default();
+    match alignment {
+        StrokeAlignment::Center => {
+            kurbo::stroke(shape.path_elements(tolerance), style, &opts, tolerance)
+        }
+        StrokeAlignment::Inside => {
+            let wide = Stroke {
+                width: style.width * 2.0,
+                ..style.clone()
+            };
+            kurbo::stroke(shape.path_elements(tolerance), &wide, &opts, tolerance)
+        }
+        StrokeAlignment::Outside => {
+            let distance = alignment.offset_distance(style.width);
+            let path = offset_path(shape, distance, style.join, style.miter_limit, tolerance);
+            kurbo::stroke(path, style, &opts, tolerance)
+        }
+    }
+}
+
+impl SceneWhisperer for Bounds {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.measure(&op, transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        for op in ops {
+            self.measure(&op, transform, shape);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, fill, push};
+    use kurbo::{Cap, Line};
+    use peniko::Color;
+
+    #[test]
+    fn bounds_of_ops() {
+        let measure = |ops: &[(PaintOpRef<'_, '_>, Affine, Rect)]| {
+            let mut bounds = Bounds::new(1e-6);
+            for &(op, transform, rect) in ops {
+                bounds.apply_paint_op(op, transform, None, &rect);
+            }
+            bounds.bounds()
+        };
+        let fill = fill(Color::BLACK);
+        let style = Stroke::new(2.0);
+        let stroke = |alignment| test_util::stroke(&style, Color::BLACK, alignment);
+        let id = Affine::IDENTITY;
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+
+        // Strokes reach out half their width from the shape, or all of it outside.
+        let stroked = |alignment| measure(&[(stroke(alignment), id, square)]);
+        assert_eq!(
+            stroked(StrokeAlignment::Center),
+            Some(Rect::new(-1.0, -1.0, 11.0, 11.0))
+        );
+        assert_eq!(stroked(StrokeAlignment::Inside), Some(square));
+        assert_eq!(
+            stroked(StrokeAlignment::Outside),
+            Some(Rect::new(-2.0, -2.0, 12.0, 12.0))
+        );
+        // Caps reach past the ends of open paths.
+        let line = |caps| {
+            let mut bounds = Bounds::new(1e-6);
+            let style = Stroke::new(4.0).with_caps(caps);
+            let op = test_util::stroke(&style, Color::BLACK, StrokeAlignment::Center);
+            bounds.apply_paint_op(op, id, None, &Line::new((0.0, 0.0), (10.0, 0.0)));
+            bounds.bounds()
+        };
+        assert_eq!(line(Cap::Butt), Some(Rect::new(0.0, -2.0, 10.0, 2.0)));
+        assert_eq!(line(Cap::Round), Some(Rect::new(-2.0, -2.0, 12.0, 2.0)));
+
+        // Transforms apply to the shape and the stroke width.
+        let scaled = Affine::translate((5.0, 5.0)) * Affine::scale(2.0);
+        assert_eq!(
+            measure(&[(fill, scaled, square)]),
+            Some(Rect::new(5.0, 5.0, 25.0, 25.0))
+        );
+        // The outline of the stroke is computed, so it is off by a rounding error.
+        let painted = measure(&[(stroke(StrokeAlignment::Center), scaled, square)]).unwrap();
+        let expected = Rect::new(3.0, 3.0, 27.0, 27.0);
+        assert!(
+            (painted.origin() - expected.origin()).hypot() < 1e-9,
+            "{painted:?}"
+        );
+        assert_eq!(painted.size().round(), expected.size());
+
+        // Layers clip what is painted inside them, and ops clipped away entirely are left out.
+        let push = push(1.0);
+        let clipped = measure(&[
+            (push, id, Rect::new(0.0, 0.0, 5.0, 5.0)),
+            (fill, id, Rect::new(2.0, 2.0, 20.0, 20.0)),
+            (
+                push,
+                Affine::translate((1.0, 1.0)),
+                Rect::new(0.0, 0.0, 10.0, 10.0),
+            ),
+            (fill, id, Rect::new(0.0, 0.0, 20.0, 20.0)),
+            (PaintOpRef::PopLayer, id, Rect::ZERO),
+            (fill, id, Rect::new(10.0, 10.0, 12.0, 12.0)),
+            (PaintOpRef::PopLayer, id, Rect::ZERO),
+        ]);
+        assert_eq!(clipped, Some(Rect::new(1.0, 1.0, 5.0, 5.0)));
+        let after = measure(&[
+            (push, id, Rect::new(0.0, 0.0, 5.0, 5.0)),
+            (PaintOpRef::PopLayer, id, Rect::ZERO),
+            (fill, id, Rect::new(10.0, 10.0, 12.0, 12.0)),
+        ]);
+        assert_eq!(after, Some(Rect::new(10.0, 10.0, 12.0, 12.0)));
+    }
+}
@@ -0,0 +1,391 @@
+//! A compact serde representation for [`StaticShape`].
+//!
+//! Paths and path segments are written as SVG path data strings, and the other shapes
+//! as short arrays of numbers, e.g. `{"Circle":[32,32,16]}` or `{"BezPath":"M10 10L15 15Z"}`.
+//! Deserialization accepts both this and the default verbose representation.
+//! At full precision, ellipses which can't be reproduced exactly from their center,
+//! radii and rotation keep the default representation.
+//!
+//! It is opted into per field, at full precision which round-trips exactly,
+//! ```
+//! # use selvage::StaticShape;
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Symbol {
+//!     #[serde(with = "selvage::compact")]
+//!     shape: StaticShape,
+//! }
+//! ```
+//! or rounded to a fixed number of decimals with `#[serde(with = "selvage::compact::Decimals::<3>")]`.
+
+use crate::shape::StaticShape;
+use kurbo::{
+    Arc, BezPath, Circle, CircleSegment, CubicBez, Ellipse, Line, PathEl, PathSeg, Point, QuadBez,
+    Rect, RoundedRect, RoundedRectRadii, Shape, Vec2,
+};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Write;
+
+/// Serializes a shape compactly at full precision.
+pub fn serialize<S: Serializer>(shape: &StaticShape, serializer: S) -> Result<S::Ok, S::Error> {
+    Compact::new(shape, None).serialize(serializer)
+}
+
+/// Deserializes a shape from either the compact or the default representation.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<StaticShape, D::Error> {
+    Repr::deserialize(deserializer)?
+        .into_shape()
+        .map_err(D::Error::custom)
+}
+
+/// The compact representation with numbers rounded to `N` decimal places.
+pub struct Decimals<const N: usize>;
+
+impl<const N: usize> Decimals<N> {
+    pub fn serialize<S: Serializer>(shape: &StaticShape, serializer: S) -> Result<S::Ok, S::Error> {
+        Compact::new(shape, Some(N)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<StaticShape, D::Error> {
+        deserialize(deserializer)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "StaticShape")]
+enum Compact {
+    PathSeg(String),
+    Arc([f64; 7]),
+    BezPath(String),
+    Circle([f64; 3]),
+    CircleSegment([f64; 6]),
+    CubicBez([f64; 8]),
+    Ellipse(EllipseRepr),
+    Line([f64; 4]),
+    QuadBez([f64; 6]),
+    Rect([f64; 4]),
+    RoundedRect(Vec<f64>),
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum EllipseRepr {
+    Params([f64; 5]),
+    Exact(Ellipse),
+}
+
+pub(crate) fn round(x: f64, decimals: Option<usize>) -> f64 {
+    match decimals {
+        Some(n) => {
+            let scale = 10f64.powi(n as i32);
+            let x = (x * scale).round() / scale;
+            // Avoid writing out `-0`.
+            if x == 0.0 {
+                0.0
+            } else {
+                x
+            }
+        }
+        None => x,
+    }
+}
+
+fn write_num(out: &mut String, x: f64, decimals: Option<usize>) {
+    let x = round(x, decimals);
+    match decimals {
+        Some(n) => {
+            let s = format!("{:.*}", n, x);
+            let s = if s.contains('.') {
+                s.trim_end_matches('0').trim_end_matches('.')
+            } else {
+                &s
+            };
+            out.push_str(s);
+        }
+        None => write!(out, "{}", x).unwrap(),
+    }
+}
+
+/// SVG path data with a single space between numbers, and none around commands.
+pub(crate) fn path_data(
+    elements: impl IntoIterator<Item = PathEl>,
+    decimals: Option<usize>,
+) -> String {
+    let mut out = String::new();
+    let points = |out: &mut String, cmd: char, pts: &[Point]| {
+        out.push(cmd);
+        for (i, p) in pts.iter().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            write_num(out, p.x, decimals);
+            out.push(' ');
+            write_num(out, p.y, decimals);
+        }
+    };
+    for el in elements {
+        match el {
+            PathEl::MoveTo(p) => points(&mut out, 'M', &[p]),
+            PathEl::LineTo(p) => points(&mut out, 'L', &[p]),
+            PathEl::QuadTo(p1, p2) => points(&mut out, 'Q', &[p1, p2]),
+            PathEl::CurveTo(p1, p2, p3) => points(&mut out, 'C', &[p1, p2, p3]),
+            PathEl::ClosePath => out.push('Z'),
+        }
+    }
+    out
+}
+
+impl Compact {
+    fn new(shape: &StaticShape, decimals: Option<usize>) -> Self {
+        use StaticShape as S;
+        let r = |x: f64| round(x, decimals);
+        match shape {
+            S::PathSeg(it) => Compact::PathSeg(path_data(it.path_elements(0.0), decimals)),
+            S::Arc(it) => Compact::Arc([
+                r(it.center.x),
+                r(it.center.y),
+                r(it.radii.x),
+                r(it.radii.y),
+                r(it.start_angle),
+                r(it.sweep_angle),
+                r(it.x_rotation),
+            ]),
+            S::BezPath(it) => Compact::BezPath(path_data(it.iter(), decimals)),
+            S::Circle(it) => Compact::Circle([r(it.center.x), r(it.center.y), r(it.radius)]),
+            S::CircleSegment(it) => Compact::CircleSegment([
+                r(it.center.x),
+                r(it.center.y),
+                r(it.outer_radius),
+                r(it.inner_radius),
+                r(it.start_angle),
+                r(it.sweep_angle),
+            ]),
+            S::CubicBez(it) => Compact::CubicBez([
+                r(it.p0.x),
+                r(it.p0.y),
+                r(it.p1.x),
+                r(it.p1.y),
+                r(it.p2.x),
+                r(it.p2.y),
+                r(it.p3.x),
+                r(it.p3.y),
+            ]),
+            S::Ellipse(it) => {
+                let center = it.center();
+                let (mut radii, mut rotation) = it.radii_and_rotation();
+                // Prefer the smallest rotation, so axis aligned ellipses come out unrotated.
+                if rotation.abs() > std::f64::consts::FRAC_PI_4 {
+                    radii = Vec2::new(radii.y, radii.x);
+                    rotation -= std::f64::consts::FRAC_PI_2.copysign(rotation);
+                }
+                // Center, radii and rotation are only used when they reproduce the ellipse
+                // exactly, otherwise it falls back to the default representation.
+                if decimals.is_some() || Ellipse::new(center, radii, rotation) == *it {
+                    Compact::Ellipse(EllipseRepr::Params(
+                        [center.x, center.y, radii.x, radii.y, rotation].map(r),
+                    ))
+                } else {
+                    Compact::Ellipse(EllipseRepr::Exact(*it))
+                }
+            }
+            S::Line(it) => Compact::Line([r(it.p0.x), r(it.p0.y), r(it.p1.x), r(it.p1.y)]),
+            S::QuadBez(it) => Compact::QuadBez([
+                r(it.p0.x),
+                r(it.p0.y),
+                r(it.p1.x),
+                r(it.p1.y),
+                r(it.p2.x),
+                r(it.p2.y),
+            ]),
+            S::Rect(it) => Compact::Rect([r(it.x0), r(it.y0), r(it.x1), r(it.y1)]),
+            S::RoundedRect(it) => {
+                let rect = it.rect();
+                let mut v: Vec<f64> = vec![rect.x0, rect.y0, rect.x1, rect.y1];
+                let radii = it.radii();
+                match radii.as_single_radius() {
+                    Some(radius) => v.push(radius),
+                    None => v.extend([
+                        radii.top_left,
+                        radii.top_right,
+                        radii.bottom_right,
+                        radii.bottom_left,
+                    ]),
+                }
+                Compact::RoundedRect(v.into_iter().map(r).collect())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Either<C, V> {
+    Compact(C),
+    Verbose(V),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "StaticShape")]
+enum Repr {
+    PathSeg(Either<String, PathSeg>),
+    Arc(Either<[f64; 7], Arc>),
+    BezPath(Either<String, BezPath>),
+    Circle(Either<[f64; 3], Circle>),
+    CircleSegment(Either<[f64; 6], CircleSegment>),
+    CubicBez(Either<[f64; 8], CubicBez>),
+    Ellipse(Either<[f64; 5], Ellipse>),
+    Line(Either<[f64; 4], Line>),
+    QuadBez(Either<[f64; 6], QuadBez>),
+    Rect(Either<[f64; 4], Rect>),
+    RoundedRect(Either<Vec<f64>, RoundedRect>),
+}
+
+impl Repr {
+    fn into_shape(self) -> Result<StaticShape, String> {
+        use Either::{Compact as C, Verbose as V};
+        use StaticShape as S;
+        Ok(match self {
+            Repr::PathSeg(C(d)) => {
+                let path = BezPath::from_svg(&d).map_err(|e| e.to_string())?;
+                let mut segs = path.segments();
+                match (segs.next(), segs.next()) {
+                    (Some(seg), None) => S::PathSeg(seg),
+                    _ => return Err(format!("expected a single path segment, found {d:?}")),
+                }
+            }
+            Repr::PathSeg(V(it)) => S::PathSeg(it),
+            Repr::Arc(C([cx, cy, rx, ry, start, sweep, rotation])) => S::Arc(Arc {
+                center: Point::new(cx, cy),
+                radii: (rx, ry).into(),
+                start_angle: start,
+                sweep_angle: sweep,
+                x_rotation: rotation,
+            }),
+            Repr::Arc(V(it)) => S::Arc(it),
+            Repr::BezPath(C(d)) => S::BezPath(BezPath::from_svg(&d).map_err(|e| e.to_string())?),
+            Repr::BezPath(V(it)) => S::BezPath(it),
+            Repr::Circle(C([cx, cy, r])) => S::Circle(Circle::new((cx, cy), r)),
+            Repr::Circle(V(it)) => S::Circle(it),
+            Repr::CircleSegment(C([cx, cy, outer, inner, start, sweep])) => {
+                S::CircleSegment(CircleSegment::new((cx, cy), outer, inner, start, sweep))
+            }
+            Repr::CircleSegment(V(it)) => S::CircleSegment(it),
+            Repr::CubicBez(C([x0, y0, x1, y1, x2, y2, x3, y3])) => {
+                S::CubicBez(CubicBez::new((x0, y0), (x1, y1), (x2, y2), (x3, y3)))
+            }
+            Repr::CubicBez(V(it)) => S::CubicBez(it),
+            Repr::Ellipse(C([cx, cy, rx, ry, rotation])) => {
+                S::Ellipse(Ellipse::new((cx, cy), (rx, ry), rotation))
+            }
+            Repr::Ellipse(V(it)) => S::Ellipse(it),
+            Repr::Line(C([x0, y0, x1, y1])) => S::Line(Line::new((x0, y0), (x1, y1))),
+            Repr::Line(V(it)) => S::Line(it),
+            Repr::QuadBez(C([x0, y0, x1, y1, x2, y2])) => {
+                S::QuadBez(QuadBez::new((x0, y0), (x1, y1), (x2, y2)))
+            }
+            Repr::QuadBez(V(it)) => S::QuadBez(it),
+            Repr::Rect(C([x0, y0, x1, y1])) => S::Rect(Rect::new(x0, y0, x1, y1)),
+            Repr::Rect(V(it)) => S::Rect(it),
+            Repr::RoundedRect(C(v)) => match v[..] {
+                [x0, y0, x1, y1, radius] => {
+                    S::RoundedRect(RoundedRect::new(x0, y0, x1, y1, radius))
+                }
+                [x0, y0, x1, y1, tl, tr, br, bl] => S::RoundedRect(RoundedRect::new(
+                    x0,
+                    y0,
+                    x1,
+                    y1,
+                    RoundedRectRadii::new(tl, tr, br, bl),
+                )),
+                _ => {
+                    return Err(format!(
+                        "expected 5 or 8 numbers for RoundedRect, found {}",
+                        v.len()
+                    ))
+                }
+            },
+            Repr::RoundedRect(V(it)) => S::RoundedRect(it),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fill_op, symbol};
+    use crate::{DisplayList, PaintOp};
+    use kurbo::Affine;
+    use peniko::Color;
+
+    #[test]
+    fn compact_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct CompactSymbol {
+            #[serde(with = "crate::compact")]
+            shape: StaticShape,
+            paint_ops: Vec<PaintOp>,
+            transform: Affine,
+        }
+
+        let path =
+            BezPath::from_svg("M0.1 0.2L10.3 0.4Q12.5 5.6 10.7 10.8C5.9 12.1 1.3 9.7 0.1 0.2Z")
+                .unwrap();
+        let shapes: Vec<StaticShape> = vec![
+            PathSeg::Line(Line::new((0.1, 0.2), (0.3, 0.4))).into(),
+            PathSeg::Quad(QuadBez::new((0.1, 0.2), (1.3, 2.4), (3.5, 0.6))).into(),
+            PathSeg::Cubic(CubicBez::new(
+                (0.1, 0.2),
+                (1.3, 2.4),
+                (3.5, 4.6),
+                (5.7, 0.8),
+            ))
+            .into(),
+            Arc::new((1.1, 2.2), (3.3, 4.4), 0.1, 2.5, 0.3).into(),
+            path.into(),
+            Circle::new((32.1, 32.2), 16.3).into(),
+            CircleSegment::new((1.1, 2.2), 5.3, 2.4, 0.5, 1.6).into(),
+            CubicBez::new((0.1, 0.2), (1.3, 2.4), (3.5, 4.6), (5.7, 0.8)).into(),
+            Ellipse::new((1.1, 2.2), (3.3, 1.4), 0.3).into(),
+            Ellipse::from_affine(Affine::new([1.1, 0.2, 0.3, 1.4, 5.0, 6.0])).into(),
+            Line::new((0.1, 0.2), (0.3, 0.4)).into(),
+            QuadBez::new((0.1, 0.2), (1.3, 2.4), (3.5, 0.6)).into(),
+            Rect::new(0.1, 0.2, 10.3, 20.4).into(),
+            RoundedRect::new(0.1, 0.2, 10.3, 20.4, 1.5).into(),
+            RoundedRect::new(
+                0.1,
+                0.2,
+                10.3,
+                20.4,
+                RoundedRectRadii::new(1.0, 2.1, 3.2, 0.3),
+            )
+            .into(),
+        ];
+        let mut list = DisplayList::new();
+        for (i, shape) in shapes.into_iter().enumerate() {
+            let paint_ops = vec![fill_op(Color::rgb8(i as u8, 0, 0))];
+            let transform = Affine::translate((i as f64 * 10.0, 0.0));
+            list.push(symbol(shape, paint_ops, transform));
+        }
+
+        let compact: Vec<CompactSymbol> = list
+            .symbols
+            .iter()
+            .map(|s| CompactSymbol {
+                shape: s.shape.clone(),
+                paint_ops: s.paint_ops.clone(),
+                transform: s.transform,
+            })
+            .collect();
+        let json = serde_json::to_string(&compact).unwrap();
+        assert!(json.contains(r#"{"Circle":[32.1,32.2,16.3]}"#), "{json}");
+        assert!(json.contains("Q12.5 5.6 10.7 10.8C"), "{json}");
+        let decoded: Vec<CompactSymbol> = serde_json::from_str(&json).unwrap();
+        let mut round_trip = DisplayList::new();
+        for s in decoded {
+            round_trip.push(symbol(s.shape, s.paint_ops, s.transform));
+        }
+        assert_eq!(round_trip, list);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::display_list::DisplayList;
+use crate::metadata::Metadata;
+use crate::offset::StrokeAlignment;
+use crate::spatial_index::SpatialIndex;
+use crate::whisperer::PaintOpRef;
+use crate::SceneWhisperer;
+use kurbo::{Affine, Cap, Join, Rect, Shape, Stroke};
+
+/// Counts of what a [`Cull`] passed on and dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullStats {
+    /// Fill and stroke ops passed on to the inner whisperer.
+    pub drawn: usize,
+    /// Fill and stroke ops dropped because they lie outside the viewport.
+    pub culled: usize,
+    /// Layers dropped along with everything inside of them.
+    pub culled_layers: usize,
+    /// Symbols skipped by a spatial index without looking at their ops.
+    pub skipped_symbols: usize,
+}
+
+/// Drops ops which can't be visible within a viewport.
+///
+/// Bounds are conservative, they are the transformed bounding box of the shape grown by
+/// the extent of the stroke, so ops that are passed on may still be invisible.
+/// Layers whose clip lies outside the viewport are dropped with their contents and the
+/// matching `PopLayer`, so pushes and pops stay balanced.
+pub struct Cull<'a, T: SceneWhisperer> {
+    whisperer: &'a mut T,
+    viewport: Rect,
+    /// The visible area within each open layer, and whether it was passed on.
+    layers: Vec<(Rect, bool)>,
+    stats: CullStats,
+}
+
+impl<'w, T: SceneWhisperer> Cull<'w, T> {
+    /// `viewport` is in the coordinate space the transforms of the ops map into.
+    pub fn new(whisperer: &'w mut T, viewport: Rect) -> Self {
+        Self {
+            whisperer,
+            viewport,
+            layers: Vec::new(),
+            stats: CullStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> CullStats {
+        self.stats
+    }
+
+    fn visible_area(&self) -> Rect {
+        self.layers.last().map_or(self.viewport, |(area, _)| *area)
+    }
+
+    /// Whether `op` should be passed on, updating the layers and statistics.
+    fn keep(&mut self, op: &PaintOpRef<'_, '_>, transform: Affine, shape: &impl Shape) -> bool {
+        let visible = self.visible_area();
+        match op {
+            PaintOpRef::PopLayer => self.layers.pop().is_none_or(|(_, kept)| kept),
+            PaintOpRef::PushLayer { .. } => {
+                let clip = transform.transform_rect_bbox(shape.bounding_box());
+                let area = visible.intersect(clip);
+                let kept = overlaps(visible, clip);
+                if !kept {
+                    self.stats.culled_layers += 1;
+                }
+                self.layers.push((area, kept));
+                kept
+            }
+            PaintOpRef::Fill { .. } | PaintOpRef::Stroke { .. } => {
+                let kept = overlaps(visible, paint_bounds(op, transform, shape));
+                if kept {
+                    self.stats.drawn += 1;
+                } else {
+                    self.stats.culled += 1;
+                }
+                kept
+            }
+        }
+    }
+}
+
+/// Whether `a` and `b` share any area, treating a zero sized `b` as a point or line.
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.width() > 0.0
+        && a.height() > 0.0
+        && b.x0 <= a.x1
+        && b.x1 >= a.x0
+        && b.y0 <= a.y1
+        && b.y1 >= a.y0
+}
+
+/// The largest factor `transform` scales any length by.
+pub(crate) fn max_scale(transform: Affine) -> f64 {
+    let [a, b, c, d, _, _] = transform.as_coeffs();
+    let sum = a * a + b * b + c * c + d * d;
+    let det = a * d - b * c;
+    ((sum + (sum * sum - 4.0 * det * det).max(0.0).sqrt()) / 2.0).sqrt()
+}
+
+/// How far a stroke can reach beyond the outline of the shape, before transforming.
+pub(crate) fn stroke_extent(style: &Stroke, alignment: StrokeAlignment) -> f64 {
+    let reach = match alignment {
+        StrokeAlignment::Center => style.width / 2.0,
+        StrokeAlignment::Inside => return 0.0,
+        StrokeAlignment::Outside => style.width,
+    };
+    let mut factor: f64 = 1.0;
+    if style.join == Join::Miter {
+        factor = factor.max(style.miter_limit);
+    }
+    if style.start_cap == Cap::Square || style.end_cap == Cap::Square {
+        factor = factor.max(std::f64::consts::SQRT_2);
+    }
+    reach * factor
+}
+
+/// A conservative bounding box of what `op` paints, after transforming.
+pub(crate) fn paint_bounds(op: &PaintOpRef<'_, '_>, transform: Affine, shape: &impl Shape) -> Rect {
+    let bounds = transform.transform_rect_bbox(shape.bounding_box());
+    match op {
+        PaintOpRef::Stroke {
+            style, alignment, ..
+        } => bounds.inflate(
+            stroke_extent(style, *alignment) * max_scale(transform),
+            stroke_extent(style, *alignment) * max_scale(transform),
+        ),
+        _ => bounds,
+    }
+}
+
+impl DisplayList {
+    /// Replays the symbols which may be visible within `viewport`, see [`Cull`].
+    ///
+    /// Large display lists only look at the symbols their cached [`SpatialIndex`] finds,
+    /// the symbols it leaves out are counted as skipped.
+    pub fn replay_culled(&self, viewport: Rect, whisperer: &mut impl SceneWhisperer) -> CullStats {
+        if let Some(index) = self.spatial_index() {
+            return self.replay_culled_in(&index, viewport, whisperer);
+        }
+        let mut cull = Cull::new(whisperer, viewport);
+        self.replay(&mut cull);
+        cull.stats()
+    }
+
+    /// Like [`DisplayList::replay_culled`], only replaying the symbols `index` finds
+    /// within `viewport`.
+    pub fn replay_culled_in(
+        &self,
+        index: &SpatialIndex,
+        viewport: Rect,
+        whisperer: &mut impl SceneWhisperer,
+    ) -> CullStats {
+        let visible = index.query_rect(viewport);
+        let mut cull = Cull::new(whisperer, viewport);
+        for &i in &visible {
+            self.symbols[i].apply(&mut cull);
+        }
+        cull.stats.skipped_symbols = self.symbols.len().saturating_sub(visible.len());
+        cull.stats()
+    }
+}
+
+impl<'w, T: SceneWhisperer> SceneWhisperer for Cull<'w, T> {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        if self.keep(&op, transform, shape) {
+            self.whisperer
+                .apply_paint_op(op, transform, brush_transform, shape);
+        }
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        let ops: Vec<_> = ops
+            .into_iter()
+            .filter(|op| self.keep(op, transform, shape))
+            .collect();
+        if !ops.is_empty() {
+            self.whisperer
+                .apply_paint_ops(ops, transform, brush_transform, shape);
+        }
+    }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        let ops: Vec<_> = ops
+            .into_iter()
+            .filter(|op| self.keep(op, transform, shape))
+            .collect();
+        if !ops.is_empty() {
+            self.whisperer.apply_paint_ops_with_metadata(
+                metadata,
+                ops,
+                transform,
+                brush_transform,
+                shape,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{push, stroke};
+    use peniko::Color;
+
+    #[test]
+    fn cull_balances_layers() {
+        let mut list = DisplayList::new();
+        let mut cull = Cull::new(&mut list, Rect::new(0.0, 0.0, 100.0, 100.0));
+        let style = Stroke::new(4.0);
+        let stroke = stroke(&style, Color::BLACK, StrokeAlignment::Center);
+        let off_screen = Rect::new(101.0, 0.0, 110.0, 10.0);
+        cull.apply_paint_op(stroke, Affine::IDENTITY, None, &off_screen);
+        cull.apply_paint_op(
+            push(1.0),
+            Affine::translate((200.0, 0.0)),
+            None,
+            &off_screen,
+        );
+        cull.apply_paint_op(stroke, Affine::IDENTITY, None, &off_screen);
+        cull.apply_paint_op(PaintOpRef::PopLayer, Affine::IDENTITY, None, &Rect::ZERO);
+        cull.apply_paint_op(stroke, Affine::translate((10.0, 0.0)), None, &off_screen);
+        let stats = cull.stats();
+        assert_eq!((stats.drawn, stats.culled, stats.culled_layers), (1, 2, 1));
+        assert_eq!(list.symbols.len(), 1);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::metadata::Metadata;
+use crate::shape::StaticShape;
+use crate::spatial_index::{SpatialIndex, INDEX_THRESHOLD};
+use crate::whisperer::{apply_paint_ops_with, PaintOp, PaintOpRef, SceneWhisperer};
+use kurbo::{Affine, Shape};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Tolerance used to convert shapes which aren't exposed by `Shape` into a `BezPath`.
+const TOLERANCE: f64 = 1e-3;
+
+/// The arguments of a single `apply_paint_ops` call.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub shape: StaticShape,
+    pub paint_ops: Vec<PaintOp>,
+    pub transform: Affine,
+    pub brush_transform: Option<Affine>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+impl Symbol {
+    pub fn apply(&self, whisperer: &mut impl SceneWhisperer) {
+        apply_paint_ops_with(
+            whisperer,
+            self.metadata.as_ref(),
+            self.paint_ops.iter().map(|x| x.into()),
+            self.transform,
+            self.brush_transform,
+            &self.shape,
+        );
+    }
+}
+
+/// The spatial index of a display list, along with the number of symbols it covers.
+///
+/// It is built when first needed, and left out of comparisons and serialization.
+#[derive(Default)]
+struct IndexCache(Mutex<Option<(usize, Arc<SpatialIndex>)>>);
+
+impl IndexCache {
+    fn lock(&self) -> MutexGuard<'_, Option<(usize, Arc<SpatialIndex>)>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for IndexCache {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.lock().clone()))
+    }
+}
+
+impl PartialEq for IndexCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for IndexCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IndexCache").finish_non_exhaustive()
+    }
+}
+
+/// A recorded scene which can be serialized and replayed through any `SceneWhisperer`.
+///
+/// Recording keeps the kind of lines, rects, rounded rects, circles and paths,
+/// other shapes are converted to a `BezPath`.
+///
+/// Large display lists keep a [`SpatialIndex`] for culling and hit testing, which is built
+/// on first use. Changing the number of `symbols` rebuilds it, after changing symbols in
+/// place call [`DisplayList::invalidate_index`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DisplayList {
+    pub symbols: Vec<Symbol>,
+    #[serde(skip)]
+    index: IndexCache,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, symbol: Symbol) {
+        self.symbols.push(symbol);
+        self.index_last();
+    }
+
+    /// Drops the cached spatial index, it is rebuilt when next needed.
+    pub fn invalidate_index(&mut self) {
+        self.index = IndexCache::default();
+    }
+
+    /// The cached spatial index, `None` for display lists small enough to scan.
+    pub(crate) fn spatial_index(&self) -> Option<Arc<SpatialIndex>> {
+        if self.symbols.len() < INDEX_THRESHOLD {
+            return None;
+        }
+        let mut cache = self.index.lock();
+        match &*cache {
+            Some((len, index)) if *len == self.symbols.len() => Some(index.clone()),
+            _ => {
+                let index = Arc::new(SpatialIndex::build(self));
+                *cache = Some((self.symbols.len(), index.clone()));
+                Some(index)
+            }
+        }
+    }
+
+    /// Adds the last symbol to the cached spatial index, if there is one covering the
+    /// symbols before it.
+    fn index_last(&mut self) {
+        let len = self.symbols.len();
+        let cache = self
+            .index
+            .0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some((indexed, index)) = cache {
+            if *indexed + 1 == len {
+                Arc::make_mut(index).insert(len - 1, &self.symbols[len - 1]);
+                *indexed = len;
+            }
+        }
+    }
+
+    /// Replays every symbol in order.
+    pub fn replay(&self, whisperer: &mut impl SceneWhisperer) {
+        for symbol in &self.symbols {
+            symbol.apply(whisperer);
+        }
+    }
+}
+
+impl SceneWhisperer for DisplayList {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.apply_paint_ops([op], transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.push(Symbol {
+            shape: StaticShape::from_shape(shape, TOLERANCE),
+            paint_ops: ops.into_iter().map(PaintOp::from).collect(),
+            transform,
+            brush_transform,
+            metadata: None,
+        });
+    }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.apply_paint_ops(ops, transform, brush_transform, shape);
+        if let Some(symbol) = self.symbols.last_mut() {
+            symbol.metadata = Some(metadata.clone());
+        }
+    }
+}
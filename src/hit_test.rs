@@ -0,0 +1,167 @@
+use crate::bounds::stroke_outline;
+use crate::display_list::{DisplayList, Symbol};
+use crate::offset::StrokeAlignment;
+use crate::spatial_index::SpatialIndex;
+use crate::whisperer::PaintOp;
+use kurbo::{BezPath, ParamCurveNearest, Point, Rect, Shape};
+use peniko::Fill;
+
+/// Tolerance used to flatten shapes and stroke outlines for hit testing.
+const TOLERANCE: f64 = 1e-3;
+
+/// An op of a recorded scene which lies under a point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Hit {
+    /// The index of the symbol in `DisplayList::symbols`.
+    pub symbol: usize,
+    /// The index of the op in the `paint_ops` of the symbol.
+    pub op: usize,
+}
+
+/// Whether `point` is inside of `path` according to `fill`, or within `tolerance` of it.
+fn path_hit(path: &BezPath, fill: Fill, point: Point, tolerance: f64) -> bool {
+    let winding = path.winding(point);
+    let inside = match fill {
+        Fill::NonZero => winding != 0,
+        Fill::EvenOdd => winding % 2 != 0,
+    };
+    inside
+        || (tolerance > 0.0
+            && path
+                .segments()
+                .any(|seg| seg.nearest(point, 1e-6).distance_sq <= tolerance * tolerance))
+}
+
+impl Symbol {
+    /// Whether the fill or stroke `op` of this symbol paints `point`, give or take `tolerance`.
+    ///
+    /// Strokes are tested against their outline, so joins, caps and dashes are respected.
+    /// Layer ops never hit.
+    pub fn hits(&self, op: &PaintOp, point: Point, tolerance: f64) -> bool {
+        match op {
+            PaintOp::Fill { style, .. } => path_hit(
+                &(self.transform * self.shape.to_path(TOLERANCE)),
+                *style,
+                point,
+                tolerance,
+            ),
+            PaintOp::Stroke {
+                style, alignment, ..
+            } => {
+                let outline = stroke_outline(&self.shape, style, *alignment, TOLERANCE);
+                path_hit(&(self.transform * outline), Fill::NonZero, point, tolerance)
+                    && (*alignment != StrokeAlignment::Inside || self.contains(point, tolerance))
+            }
+            PaintOp::PushLayer { .. } | PaintOp::PopLayer => false,
+        }
+    }
+
+    /// Whether `point` is within the transformed shape, give or take `tolerance`.
+    fn contains(&self, point: Point, tolerance: f64) -> bool {
+        path_hit(
+            &(self.transform * self.shape.to_path(TOLERANCE)),
+            Fill::NonZero,
+            point,
+            tolerance,
+        )
+    }
+}
+
+impl DisplayList {
+    /// The fill and stroke ops painted at `point`, topmost first.
+    ///
+    /// Ops within `tolerance` of the point count as hits, which helps with touch input.
+    /// Ops inside of a layer only hit where the layer clip does.
+    /// Large display lists only test the symbols their cached [`SpatialIndex`] finds.
+    pub fn hit_test(&self, point: Point, tolerance: f64) -> Vec<Hit> {
+        if let Some(index) = self.spatial_index() {
+            return self.hit_test_in(&index, point, tolerance);
+        }
+        self.hit_test_symbols(0..self.symbols.len(), point, tolerance)
+    }
+
+    /// Like [`DisplayList::hit_test`], only testing the symbols `index` finds near `point`.
+    pub fn hit_test_in(&self, index: &SpatialIndex, point: Point, tolerance: f64) -> Vec<Hit> {
+        let near = Rect::from_points(point, point).inflate(tolerance, tolerance);
+        self.hit_test_symbols(index.query_rect(near), point, tolerance)
+    }
+
+    /// The topmost op painted at `point`, see [`DisplayList::hit_test`].
+    pub fn hit_test_topmost(&self, point: Point, tolerance: f64) -> Option<Hit> {
+        self.hit_test(point, tolerance).into_iter().next()
+    }
+
+    /// Tests the symbols at `indices`, which must be in paint order.
+    fn hit_test_symbols(
+        &self,
+        indices: impl IntoIterator<Item = usize>,
+        point: Point,
+        tolerance: f64,
+    ) -> Vec<Hit> {
+        let mut hits = Vec::new();
+        // Whether the point is inside the clips of each layer which hasn't been popped.
+        let mut layers: Vec<bool> = Vec::new();
+        for i in indices {
+            let symbol = &self.symbols[i];
+            for (j, op) in symbol.paint_ops.iter().enumerate() {
+                let inside = layers.last().copied().unwrap_or(true);
+                match op {
+                    PaintOp::PushLayer { .. } => {
+                        layers.push(inside && symbol.contains(point, tolerance))
+                    }
+                    PaintOp::PopLayer => {
+                        layers.pop();
+                    }
+                    PaintOp::Fill { .. } | PaintOp::Stroke { .. } => {
+                        if inside && symbol.hits(op, point, tolerance) {
+                            hits.push(Hit { symbol: i, op: j });
+                        }
+                    }
+                }
+            }
+        }
+        hits.reverse();
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fill_op, stroke_op, symbol};
+    use kurbo::{Affine, Circle};
+    use peniko::Color;
+
+    #[test]
+    fn hit_test_ring() {
+        let mut list = DisplayList::new();
+        let square = Rect::new(0.0, 0.0, 100.0, 100.0);
+        list.push(symbol(
+            square,
+            vec![fill_op(Color::BLACK)],
+            Affine::IDENTITY,
+        ));
+        let ring = stroke_op(2.0, Color::BLACK, StrokeAlignment::Center);
+        let circle = Circle::new((0.0, 0.0), 10.0);
+        list.push(symbol(circle, vec![ring], Affine::translate((50.0, 50.0))));
+        let hit = |x, y, tolerance| list.hit_test(Point::new(x, y), tolerance);
+        assert_eq!(
+            hit(60.5, 50.0, 0.0),
+            vec![Hit { symbol: 1, op: 0 }, Hit { symbol: 0, op: 0 }]
+        );
+        assert_eq!(hit(50.0, 50.0, 0.0), vec![Hit { symbol: 0, op: 0 }]);
+        assert_eq!(hit(62.0, 50.0, 0.0).len(), 1);
+        assert_eq!(hit(62.0, 50.0, 1.5).len(), 2);
+        assert!(hit(101.0, 50.0, 0.0).is_empty());
+
+        // Inside strokes cover their whole width within the shape, and nothing outside.
+        let mut list = DisplayList::new();
+        let inside = stroke_op(10.0, Color::BLACK, StrokeAlignment::Inside);
+        list.push(symbol(square, vec![inside], Affine::IDENTITY));
+        let hit = |x, y| list.hit_test(Point::new(x, y), 0.0).len();
+        assert_eq!(hit(3.0, 50.0), 1);
+        assert_eq!(hit(7.0, 50.0), 1);
+        assert_eq!(hit(11.0, 50.0), 0);
+        assert_eq!(hit(-3.0, 50.0), 0);
+    }
+}
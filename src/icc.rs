@@ -0,0 +1,123 @@
+#![cfg(feature = "pdf")]
+//! Minimal ICC version 2 display profiles for RGB color spaces.
+use crate::print_color::srgb_decode;
+
+/// An RGB color space, relative to the D50 profile connection space.
+pub(crate) struct RgbSpace {
+    pub description: &'static str,
+    /// The XYZ coordinates of the red, green and blue primaries, adapted to D50.
+    pub primaries: [[f64; 3]; 3],
+    /// Converts an encoded component to linear light.
+    pub decode: fn(f64) -> f64,
+}
+
+pub(crate) const SRGB: RgbSpace = RgbSpace {
+    description: "sRGB IEC61966-2.1",
+    primaries: [
+        [0.436065, 0.222488, 0.013916],
+        [0.385147, 0.716873, 0.097076],
+        [0.143066, 0.060608, 0.714096],
+    ],
+    decode: srgb_decode,
+};
+
+pub(crate) const DISPLAY_P3: RgbSpace = RgbSpace {
+    description: "Display P3",
+    primaries: [
+        [0.515102, 0.241182, -0.001050],
+        [0.291965, 0.692236, 0.041881],
+        [0.157153, 0.066583, 0.784378],
+    ],
+    decode: srgb_decode,
+};
+
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// The number of entries in the sampled tone reproduction curve.
+const CURVE_LEN: usize = 1024;
+
+fn s15_fixed16(x: f64) -> [u8; 4] {
+    ((x * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz(out: &mut Vec<u8>, [x, y, z]: [f64; 3]) {
+    out.extend(b"XYZ \0\0\0\0");
+    for v in [x, y, z] {
+        out.extend(s15_fixed16(v));
+    }
+}
+
+impl RgbSpace {
+    /// The profile, with the curve of each channel sampled.
+    pub fn profile(&self) -> Vec<u8> {
+        let mut desc = b"desc\0\0\0\0".to_vec();
+        desc.extend((self.description.len() as u32 + 1).to_be_bytes());
+        desc.extend(self.description.as_bytes());
+        // The terminator, empty Unicode and ScriptCode descriptions.
+        desc.extend([0; 1 + 8 + 3 + 67]);
+        let mut cprt = b"text\0\0\0\0".to_vec();
+        cprt.extend(b"No copyright, use freely\0");
+        let mut white = Vec::new();
+        xyz(&mut white, D50);
+        let [r, g, b] = self.primaries.map(|primary| {
+            let mut out = Vec::new();
+            xyz(&mut out, primary);
+            out
+        });
+        let mut curve = b"curv\0\0\0\0".to_vec();
+        curve.extend((CURVE_LEN as u32).to_be_bytes());
+        for i in 0..CURVE_LEN {
+            let y = (self.decode)(i as f64 / (CURVE_LEN - 1) as f64);
+            curve.extend(((y.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes());
+        }
+
+        // The channels share a single curve.
+        let tags: [(&[u8; 4], usize); 9] = [
+            (b"desc", 0),
+            (b"cprt", 1),
+            (b"wtpt", 2),
+            (b"rXYZ", 3),
+            (b"gXYZ", 4),
+            (b"bXYZ", 5),
+            (b"rTRC", 6),
+            (b"gTRC", 6),
+            (b"bTRC", 6),
+        ];
+        let data = [desc, cprt, white, r, g, b, curve];
+        let mut offsets = Vec::new();
+        let mut offset = 128 + 4 + 12 * tags.len();
+        for block in &data {
+            offsets.push(offset);
+            offset += block.len().next_multiple_of(4);
+        }
+
+        let mut out = Vec::with_capacity(offset);
+        out.extend((offset as u32).to_be_bytes());
+        out.extend([0; 4]);
+        out.extend([2, 0x10, 0, 0]);
+        out.extend(b"mntrRGB XYZ ");
+        // Creation date, 2000-01-01.
+        for x in [2000u16, 1, 1, 0, 0, 0] {
+            out.extend(x.to_be_bytes());
+        }
+        out.extend(b"acsp");
+        out.extend([0; 24]);
+        // Perceptual rendering intent.
+        out.extend([0; 4]);
+        for v in D50 {
+            out.extend(s15_fixed16(v));
+        }
+        out.resize(128, 0);
+        out.extend((tags.len() as u32).to_be_bytes());
+        for (signature, block) in tags {
+            out.extend(signature);
+            out.extend((offsets[block] as u32).to_be_bytes());
+            out.extend((data[block].len() as u32).to_be_bytes());
+        }
+        for block in &data {
+            out.extend(block);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+}
@@ -2,21 +2,67 @@ mod whisperer;
 pub use crate::whisperer::{PaintOp, PaintOpRef, SceneWhisperer};
 mod shape;
 pub use shape::StaticShape;
+mod display_list;
+pub use display_list::{DisplayList, Symbol};
+pub mod compact;
+mod offset;
+pub use offset::{offset_path, StrokeAlignment};
 mod shape_transform;
 pub use shape_transform::ShapeTransform;
+mod bounds;
+pub use bounds::Bounds;
+mod cull;
+pub use cull::{Cull, CullStats};
+mod hit_test;
+pub use hit_test::Hit;
+mod icc;
+mod metadata;
+pub use metadata::{Metadata, ViewerLayer};
+mod optimize;
+pub use optimize::{OptimizeReport, Optimizer};
+mod pixel_snap;
+pub use pixel_snap::PixelSnap;
+mod print_color;
+pub use print_color::{PrintColor, ProcessColor};
+mod spatial_index;
+pub use spatial_index::SpatialIndex;
+mod tee;
+pub use tee::DynSceneWhisperer;
+mod trace;
+pub use trace::{Overlay, Trace};
 
 #[cfg(feature = "svg")]
 mod svg;
 #[cfg(feature = "vello")]
 mod vello_whisperer;
 #[cfg(feature = "svg")]
-pub use svg::Svg;
+pub use svg::{Svg, SvgOptions, SvgWriter};
 
+#[cfg(feature = "svg-import")]
+mod svg_import;
+#[cfg(feature = "svg-import")]
+pub use svg_import::{import_svg, Diagnostic, ImportedSvg, Unsupported};
+
+#[cfg(feature = "pdf")]
+mod pdf_content;
+#[cfg(feature = "pdf")]
+pub use pdf_content::{PdfContent, PdfResources};
+#[cfg(feature = "pdf")]
+mod pdf_marks;
+#[cfg(feature = "pdf")]
+pub use pdf_marks::PrintSetup;
+#[cfg(feature = "pdf")]
+mod pdf_tiles;
+#[cfg(feature = "pdf")]
+pub use pdf_tiles::{TileOptions, TiledPdf};
 #[cfg(feature = "pdf")]
 mod pdf_whisperer;
 #[cfg(feature = "pdf")]
 pub use pdf_whisperer::*;
 
+#[cfg(test)]
+mod test_util;
+
 #[cfg(test)]
 mod tests {
     //use super::*;
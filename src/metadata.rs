@@ -0,0 +1,247 @@
+use crate::print_color::PrintColor;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What a group of ops stands for, passed to
+/// [`SceneWhisperer::apply_paint_ops_with_metadata`](crate::SceneWhisperer::apply_paint_ops_with_metadata).
+///
+/// Backends without a way to express it ignore it. SVG output gets an `id`, `class`,
+/// `data-*` attributes, `<title>` and `<desc>` elements, an `<a href>` and Inkscape layers,
+/// PDF output gets link annotations, named destinations, bookmarks, optional content and
+/// print colors.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// An identifier which should be unique within the document, which links can go to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// A URL the painted area links to, or `#` followed by the `id` of an area in the same
+    /// document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Arbitrary key value pairs, keys should be valid in an XML attribute name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, String>,
+    /// The title of an entry for the painted area in the outline of the document, nested
+    /// under the entries of the layers it is painted in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bookmark: Option<String>,
+    /// The name of the [`ViewerLayer`] the ops are on, layers which pass a group of ops
+    /// along in a later call are on it along with their contents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    /// The color solid fills are printed in. When replaying a display list, solid brushes
+    /// are replaced with its sRGB approximation, keeping their alpha, for backends which
+    /// can't print it.
+    ///
+    /// Print colors aren't a kind of brush, the PDF backends take them from the metadata
+    /// and replaying substitutes them for the others. Ops given to other backends directly
+    /// are painted with their brushes as they are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill_color: Option<PrintColor>,
+    /// The color solid strokes are printed in, like `fill_color`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke_color: Option<PrintColor>,
+    /// Whether fills and strokes leave the inks they don't use beneath them, rather than
+    /// knocking them out.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub overprint: bool,
+}
+
+/// A layer viewers let the user show and hide, such as dimensions or a background grid.
+///
+/// Backends are given the layers of a document in the order they should be listed in,
+/// ops are put on them with [`Metadata::layer`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ViewerLayer {
+    pub name: String,
+    /// Whether the layer is shown when the document is opened.
+    pub visible: bool,
+}
+
+impl ViewerLayer {
+    /// A layer which is visible initially.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+        }
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn with_class(mut self, class: impl Into<String>) -> Self {
+        self.classes.push(class.into());
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_link(mut self, link: impl Into<String>) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    pub fn with_data(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.data.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_bookmark(mut self, title: impl Into<String>) -> Self {
+        self.bookmark = Some(title.into());
+        self
+    }
+
+    pub fn with_layer(mut self, layer: impl Into<String>) -> Self {
+        self.layer = Some(layer.into());
+        self
+    }
+
+    pub fn with_fill_color(mut self, color: PrintColor) -> Self {
+        self.fill_color = Some(color);
+        self
+    }
+
+    pub fn with_stroke_color(mut self, color: PrintColor) -> Self {
+        self.stroke_color = Some(color);
+        self
+    }
+
+    pub fn with_overprint(mut self) -> Self {
+        self.overprint = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fill;
+    use crate::{DisplayList, SceneWhisperer};
+    use kurbo::{Affine, Circle};
+    use peniko::Color;
+
+    #[test]
+    fn metadata_is_recorded() {
+        let metadata = Metadata::new()
+            .with_id("n1")
+            .with_class("node")
+            .with_class("big")
+            .with_title("A & B")
+            .with_link("https://example.com/?a=1&b=2")
+            .with_data("kind", "server");
+        let fill = fill(Color::BLACK);
+        let circle = Circle::new((10.0, 10.0), 5.0);
+        let mut list = DisplayList::new();
+        list.apply_paint_ops_with_metadata(&metadata, [fill], Affine::IDENTITY, None, &circle);
+        list.apply_paint_op(fill, Affine::IDENTITY, None, &circle);
+        assert_eq!(list.symbols[0].metadata.as_ref(), Some(&metadata));
+        assert_eq!(list.symbols[1].metadata, None);
+        let mut copy = DisplayList::new();
+        list.replay(&mut copy);
+        assert_eq!(copy, list);
+
+        #[cfg(feature = "svg")]
+        {
+            let mut svg = crate::Svg::new(kurbo::Size::new(20.0, 20.0));
+            list.replay(&mut svg);
+            let svg = svg.display().to_string();
+            assert!(svg.contains(
+                "<a class=\"node big\" data-kind=\"server\" \
+                 href=\"https://example.com/?a=1&amp;b=2\" id=\"n1\">\n<title>A &amp; B</title>\n<circle"
+            ));
+        }
+        #[cfg(feature = "pdf")]
+        {
+            let mut pdf = crate::Pdf::new(kurbo::Size::new(20.0, 20.0), 0.1);
+            list.replay(&mut pdf);
+            let out = crate::test_util::written(pdf);
+            assert!(out.contains("/Subtype /Link"));
+            assert!(out.contains("/Rect [5 5 15 15]"));
+        }
+    }
+
+    #[cfg(all(feature = "svg", feature = "pdf"))]
+    #[test]
+    fn viewer_layers() {
+        use crate::test_util::{assert_contains, push, uncompressed, written};
+        use crate::{PaintOpRef, Pdf, PdfOptions, Svg, SvgOptions};
+        use kurbo::{Rect, Size};
+        let layers = vec![
+            ViewerLayer::new("Drawing"),
+            ViewerLayer::new("Grid").hidden(),
+        ];
+        fn draw(whisperer: &mut impl SceneWhisperer) {
+            let fill = fill(Color::BLACK);
+            let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+            let grid = Metadata::new().with_layer("Grid");
+            whisperer.apply_paint_ops_with_metadata(&grid, [fill], Affine::IDENTITY, None, &square);
+            let drawing = Metadata::new().with_layer("Drawing");
+            let ops = [push(1.0)];
+            whisperer.apply_paint_ops_with_metadata(&drawing, ops, Affine::IDENTITY, None, &square);
+            whisperer.apply_paint_op(fill, Affine::IDENTITY, None, &square);
+            whisperer.apply_paint_op(PaintOpRef::PopLayer, Affine::IDENTITY, None, &square);
+        }
+
+        let options = SvgOptions {
+            layers: layers.clone(),
+            ..SvgOptions::default()
+        };
+        let mut svg = Svg::with_options(Size::new(10.0, 10.0), options);
+        draw(&mut svg);
+        let out = svg.display().to_string();
+        assert!(out.contains("xmlns:inkscape="));
+        assert!(out.contains(
+            r#"<g inkscape:groupmode="layer" inkscape:label="Grid" style="display:none">"#
+        ));
+        assert_eq!(out.matches(r#"inkscape:label="Drawing""#).count(), 1);
+        assert!(out.ends_with("</g>\n</g>\n</svg>"));
+
+        let options = PdfOptions {
+            layers,
+            ..uncompressed()
+        };
+        let mut pdf = Pdf::with_options(Size::new(10.0, 10.0), 0.1, options);
+        draw(&mut pdf);
+        assert_contains(
+            &written(pdf),
+            &[
+                "/OC /OC0 BDC",
+                "/OC /OC1 BDC\nq",
+                "Q\nEMC",
+                "/Type /OCG",
+                "/Name (Grid)",
+                "/OCProperties",
+                "/Order [",
+                "/OFF [",
+            ],
+        );
+    }
+}
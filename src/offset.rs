@@ -0,0 +1,324 @@
+use kurbo::offset::CubicOffset;
+use kurbo::{
+    fit_to_bezpath, Arc, BezPath, CubicBez, Join, Line, ParamCurve, ParamCurveArea, PathEl,
+    PathSeg, Point, Shape, Vec2,
+};
+use serde::{Deserialize, Serialize};
+
+/// Where a stroke lies relative to the outline of the shape it is painted on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StrokeAlignment {
+    /// Centered on the outline, which is what every backend does natively.
+    #[default]
+    Center,
+    /// Entirely within the shape, like an inside border.
+    Inside,
+    /// Entirely outside of the shape, like an outside border.
+    Outside,
+}
+
+impl StrokeAlignment {
+    /// The distance a centered stroke of `width` has to be offset by to get this alignment.
+    pub fn offset_distance(self, width: f64) -> f64 {
+        match self {
+            StrokeAlignment::Center => 0.0,
+            StrokeAlignment::Inside => -width / 2.0,
+            StrokeAlignment::Outside => width / 2.0,
+        }
+    }
+}
+
+struct Subpath {
+    segs: Vec<PathSeg>,
+    closed: bool,
+}
+
+/// Offsets each subpath of `shape` by `distance`.
+///
+/// Closed subpaths grow outward for positive distances and shrink for negative ones,
+/// regardless of their winding direction. Open subpaths are offset along the normal
+/// `(-dy, dx)` of their direction.
+///
+/// Corners on the outside of the offset are filled in with `join`, inside corners
+/// between two lines are trimmed to their intersection, and other inside corners are
+/// routed through the original vertex which doesn't change the nonzero fill of the result.
+pub fn offset_path(
+    shape: &impl Shape,
+    distance: f64,
+    join: Join,
+    miter_limit: f64,
+    tolerance: f64,
+) -> BezPath {
+    let path = shape.to_path(tolerance);
+    if distance == 0.0 {
+        return path;
+    }
+    let mut out = BezPath::new();
+    for sub in subpaths(&path) {
+        let area: f64 = sub.segs.iter().map(|seg| seg.signed_area()).sum();
+        let side = if sub.closed && area != 0.0 {
+            -area.signum() * distance
+        } else {
+            distance
+        };
+        offset_subpath(&mut out, &sub, side, join, miter_limit, tolerance);
+    }
+    out
+}
+
+fn subpaths(path: &BezPath) -> Vec<Subpath> {
+    let mut out = Vec::new();
+    let mut segs = Vec::new();
+    let mut start = Point::ZERO;
+    let mut last = Point::ZERO;
+    for el in path.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                if !segs.is_empty() {
+                    out.push(Subpath {
+                        segs: std::mem::take(&mut segs),
+                        closed: false,
+                    });
+                }
+                start = p;
+                last = p;
+            }
+            PathEl::LineTo(p) => {
+                if p != last {
+                    segs.push(PathSeg::Line(Line::new(last, p)));
+                }
+                last = p;
+            }
+            PathEl::QuadTo(p1, p2) => {
+                if p1 != last || p2 != last {
+                    segs.push(PathSeg::Cubic(kurbo::QuadBez::new(last, p1, p2).raise()));
+                }
+                last = p2;
+            }
+            PathEl::CurveTo(p1, p2, p3) => {
+                if p1 != last || p2 != last || p3 != last {
+                    segs.push(PathSeg::Cubic(CubicBez::new(last, p1, p2, p3)));
+                }
+                last = p3;
+            }
+            PathEl::ClosePath => {
+                if last != start {
+                    segs.push(PathSeg::Line(Line::new(last, start)));
+                }
+                if !segs.is_empty() {
+                    out.push(Subpath {
+                        segs: std::mem::take(&mut segs),
+                        closed: true,
+                    });
+                }
+                last = start;
+            }
+        }
+    }
+    if !segs.is_empty() {
+        out.push(Subpath {
+            segs,
+            closed: false,
+        });
+    }
+    out
+}
+
+fn tangents(seg: &PathSeg) -> (Vec2, Vec2) {
+    fn first_nonzero(candidates: [Vec2; 3]) -> Vec2 {
+        candidates
+            .into_iter()
+            .find(|v| *v != Vec2::ZERO)
+            .unwrap_or(Vec2::ZERO)
+    }
+    match *seg {
+        PathSeg::Line(l) => (l.p1 - l.p0, l.p1 - l.p0),
+        PathSeg::Quad(q) => (
+            first_nonzero([q.p1 - q.p0, q.p2 - q.p0, q.p2 - q.p0]),
+            first_nonzero([q.p2 - q.p1, q.p2 - q.p0, q.p2 - q.p0]),
+        ),
+        PathSeg::Cubic(c) => (
+            first_nonzero([c.p1 - c.p0, c.p2 - c.p0, c.p3 - c.p0]),
+            first_nonzero([c.p3 - c.p2, c.p3 - c.p1, c.p3 - c.p0]),
+        ),
+    }
+}
+
+fn normal(tangent: Vec2) -> Vec2 {
+    Vec2::new(-tangent.y, tangent.x).normalize()
+}
+
+/// The offset of a single segment as a path starting with a `MoveTo`.
+fn offset_seg(seg: &PathSeg, side: f64, tolerance: f64) -> BezPath {
+    match *seg {
+        PathSeg::Line(l) => {
+            let n = normal(l.p1 - l.p0) * side;
+            let mut path = BezPath::new();
+            path.move_to(l.p0 + n);
+            path.line_to(l.p1 + n);
+            path
+        }
+        PathSeg::Quad(q) => offset_seg(&PathSeg::Cubic(q.raise()), side, tolerance),
+        PathSeg::Cubic(c) => {
+            fit_to_bezpath(&CubicOffset::new_regularized(c, side, tolerance), tolerance)
+        }
+    }
+}
+
+fn as_line(piece: &BezPath) -> Option<Line> {
+    match piece.elements() {
+        [PathEl::MoveTo(p0), PathEl::LineTo(p1)] => Some(Line::new(*p0, *p1)),
+        _ => None,
+    }
+}
+
+/// Trims two consecutive offset lines to their intersection when it lies on both.
+fn trim_lines(a: &mut BezPath, b: &mut BezPath) {
+    let (Some(la), Some(lb)) = (as_line(a), as_line(b)) else {
+        return;
+    };
+    let da = la.p1 - la.p0;
+    let db = lb.p1 - lb.p0;
+    let denom = da.cross(db);
+    if denom == 0.0 {
+        return;
+    }
+    let ta = (lb.p0 - la.p0).cross(db) / denom;
+    let tb = (lb.p0 - la.p0).cross(da) / denom;
+    if (0.0..=1.0).contains(&ta) && (0.0..=1.0).contains(&tb) {
+        let x = la.eval(ta);
+        *a = BezPath::from_vec(vec![PathEl::MoveTo(la.p0), PathEl::LineTo(x)]);
+        *b = BezPath::from_vec(vec![PathEl::MoveTo(x), PathEl::LineTo(lb.p1)]);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_join(
+    out: &mut BezPath,
+    vertex: Point,
+    tan_in: Vec2,
+    tan_out: Vec2,
+    target: Point,
+    side: f64,
+    join: Join,
+    miter_limit: f64,
+    tolerance: f64,
+) {
+    let Some(current) = out.elements().last().and_then(|el| el.end_point()) else {
+        return;
+    };
+    if current.distance(target) <= tolerance {
+        if current != target {
+            out.line_to(target);
+        }
+        return;
+    }
+    let cross = tan_in.cross(tan_out);
+    if cross * side >= 0.0 {
+        // The inside of a corner, where the offset segments overlap.
+        out.line_to(vertex);
+        out.line_to(target);
+        return;
+    }
+    match join {
+        Join::Bevel => out.line_to(target),
+        Join::Miter => {
+            let dot = tan_in.dot(tan_out);
+            let hypot = cross.hypot(dot);
+            if 2.0 * hypot < (hypot + dot) * miter_limit.powi(2) {
+                let h = (target - current).cross(tan_out) / cross;
+                out.line_to(current + tan_in * h);
+            }
+            out.line_to(target);
+        }
+        Join::Round => {
+            let v0 = current - vertex;
+            let v1 = target - vertex;
+            let sweep = v0.cross(v1).atan2(v0.dot(v1));
+            let radius = side.abs();
+            let arc = Arc::new(vertex, (radius, radius), v0.atan2(), sweep, 0.0);
+            out.extend(arc.append_iter(tolerance));
+            out.line_to(target);
+        }
+    }
+}
+
+fn offset_subpath(
+    out: &mut BezPath,
+    sub: &Subpath,
+    side: f64,
+    join: Join,
+    miter_limit: f64,
+    tolerance: f64,
+) {
+    let mut pieces: Vec<BezPath> = sub
+        .segs
+        .iter()
+        .map(|seg| offset_seg(seg, side, tolerance))
+        .collect();
+    let n = pieces.len();
+    let joints = if sub.closed { n } else { n - 1 };
+    for i in 0..joints {
+        let j = (i + 1) % n;
+        if i == j {
+            continue;
+        }
+        let (a, b) = if i < j {
+            let (head, tail) = pieces.split_at_mut(j);
+            (&mut head[i], &mut tail[0])
+        } else {
+            let (head, tail) = pieces.split_at_mut(i);
+            (&mut tail[0], &mut head[j])
+        };
+        trim_lines(a, b);
+    }
+
+    let start = |piece: &BezPath| piece.elements()[0].end_point().unwrap();
+    out.move_to(start(&pieces[0]));
+    for (i, piece) in pieces.iter().enumerate() {
+        if i > 0 {
+            add_join(
+                out,
+                sub.segs[i].start(),
+                tangents(&sub.segs[i - 1]).1,
+                tangents(&sub.segs[i]).0,
+                start(piece),
+                side,
+                join,
+                miter_limit,
+                tolerance,
+            );
+        }
+        out.extend(piece.elements()[1..].iter().copied());
+    }
+    if sub.closed {
+        add_join(
+            out,
+            sub.segs[0].start(),
+            tangents(&sub.segs[n - 1]).1,
+            tangents(&sub.segs[0]).0,
+            start(&pieces[0]),
+            side,
+            join,
+            miter_limit,
+            tolerance,
+        );
+        out.close_path();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurbo::Rect;
+
+    #[test]
+    fn offset_rect() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let area = |d, join| offset_path(&rect, d, join, 4.0, 1e-3).area();
+        assert!((area(1.0, Join::Miter) - 144.0).abs() < 1e-9);
+        assert!((area(1.0, Join::Bevel) - 142.0).abs() < 1e-9);
+        assert!((area(1.0, Join::Round) - (140.0 + std::f64::consts::PI)).abs() < 1e-3);
+        assert!((area(-1.0, Join::Miter) - 64.0).abs() < 1e-9);
+    }
+}
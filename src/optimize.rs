@@ -0,0 +1,342 @@
+use crate::cull::paint_bounds;
+use crate::display_list::{DisplayList, Symbol};
+use crate::shape::StaticShape;
+use crate::whisperer::PaintOp;
+use kurbo::{Affine, BezPath, Circle, Rect, RoundedRect, Shape, Vec2};
+use peniko::Brush;
+use std::collections::HashSet;
+
+/// Passes which simplify a display list without changing what it paints.
+///
+/// Each pass can be turned off, they run in the order of the fields.
+#[derive(Clone, Debug)]
+pub struct Optimizer {
+    /// Removes fills and strokes with fully transparent brushes, fills of shapes without
+    /// area, and layers with zero alpha along with their contents.
+    pub remove_invisible: bool,
+    /// Removes symbols identical to the one before them, when they only paint opaque colors.
+    pub remove_duplicates: bool,
+    /// Applies transforms to the shapes, when that doesn't change stroke widths or turn
+    /// rects into paths, and drops identity brush transforms.
+    pub fold_transforms: bool,
+    /// Merges adjacent symbols with the same ops, transforms and metadata into one `BezPath`,
+    /// when they don't overlap or only stroke opaque colors.
+    pub merge_paths: bool,
+    /// Removes layers which don't paint anything.
+    pub remove_empty_layers: bool,
+    /// Tolerance used to convert shapes into a `BezPath`.
+    pub tolerance: f64,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self {
+            remove_invisible: true,
+            remove_duplicates: true,
+            fold_transforms: true,
+            merge_paths: true,
+            remove_empty_layers: true,
+            tolerance: 1e-3,
+        }
+    }
+}
+
+/// What an [`Optimizer`] changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OptimizeReport {
+    /// Ops removed because they don't paint anything, including those in invisible layers.
+    pub invisible_ops: usize,
+    pub duplicate_symbols: usize,
+    /// Symbols whose transform was applied to the shape.
+    pub folded_transforms: usize,
+    /// Symbols merged into the symbol before them.
+    pub merged_symbols: usize,
+    pub empty_layers: usize,
+}
+
+fn is_transparent(brush: &Brush) -> bool {
+    match brush {
+        Brush::Solid(color) => color.a == 0,
+        Brush::Gradient(gradient) => gradient.stops.iter().all(|stop| stop.color.a == 0),
+        Brush::Image(image) => image.alpha == 0,
+    }
+}
+
+fn is_opaque_solid(op: &PaintOp) -> bool {
+    match op {
+        PaintOp::Fill { brush, .. } | PaintOp::Stroke { brush, .. } => {
+            matches!(brush, Brush::Solid(color) if color.a == 255)
+        }
+        PaintOp::PushLayer { .. } | PaintOp::PopLayer => false,
+    }
+}
+
+fn is_invisible(op: &PaintOp, shape: &StaticShape) -> bool {
+    match op {
+        PaintOp::Fill { brush, .. } => {
+            let bounds = shape.bounding_box();
+            is_transparent(brush) || bounds.width() == 0.0 || bounds.height() == 0.0
+        }
+        PaintOp::Stroke { brush, .. } => is_transparent(brush),
+        PaintOp::PushLayer { .. } | PaintOp::PopLayer => false,
+    }
+}
+
+/// `shape` with `transform` applied, if it keeps its kind.
+fn fold_shape(shape: &StaticShape, transform: Affine, tolerance: f64) -> Option<StaticShape> {
+    use StaticShape as S;
+    let [a, b, c, d, e, f] = transform.as_coeffs();
+    let translation = [a, b, c, d] == [1.0, 0.0, 0.0, 1.0];
+    let axis_aligned = b == 0.0 && c == 0.0;
+    let offset = Vec2::new(e, f);
+    Some(match shape {
+        S::Rect(it) if translation => S::Rect(*it + offset),
+        S::Rect(it) if axis_aligned => S::Rect(transform.transform_rect_bbox(*it)),
+        S::RoundedRect(it) if translation => {
+            S::RoundedRect(RoundedRect::from_rect(it.rect() + offset, it.radii()))
+        }
+        S::Circle(it) if translation => S::Circle(Circle::new(it.center + offset, it.radius)),
+        S::Rect(_) | S::RoundedRect(_) | S::CircleSegment(_) => return None,
+        _ => shape.clone().apply_transform(transform, tolerance),
+    })
+}
+
+/// A conservative bounding box of everything `symbol` paints.
+fn symbol_bounds(symbol: &Symbol) -> Rect {
+    symbol
+        .paint_ops
+        .iter()
+        .map(|op| paint_bounds(&op.into(), symbol.transform, &symbol.shape))
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO)
+}
+
+fn disjoint(a: Rect, b: Rect) -> bool {
+    a.x1 < b.x0 || b.x1 < a.x0 || a.y1 < b.y0 || b.y1 < a.y0
+}
+
+/// Whether `b` can be merged into `a` without changing what they paint.
+fn can_merge(a: &Symbol, b: &Symbol) -> bool {
+    if a.paint_ops != b.paint_ops
+        || a.transform != b.transform
+        || a.brush_transform != b.brush_transform
+        || a.metadata != b.metadata
+    {
+        return false;
+    }
+    let mut strokes_only = true;
+    for op in &a.paint_ops {
+        match op {
+            // Rather than relying on every backend restarting dashes on each subpath.
+            PaintOp::Stroke { style, .. } if !style.dash_pattern.is_empty() => return false,
+            PaintOp::Stroke { .. } => (),
+            PaintOp::Fill { .. } => strokes_only = false,
+            PaintOp::PushLayer { .. } | PaintOp::PopLayer => return false,
+        }
+    }
+    // Overlapping fills could change the winding, and overlapping translucent strokes
+    // would only be painted once.
+    (strokes_only && a.paint_ops.iter().all(is_opaque_solid))
+        || disjoint(symbol_bounds(a), symbol_bounds(b))
+}
+
+impl Optimizer {
+    pub fn optimize(&self, list: &mut DisplayList) -> OptimizeReport {
+        let mut report = OptimizeReport::default();
+        if self.remove_invisible {
+            report.invisible_ops = remove_invisible(list);
+        }
+        if self.remove_duplicates {
+            let before = list.symbols.len();
+            list.symbols.dedup_by(|b, a| {
+                a == b && !a.paint_ops.is_empty() && a.paint_ops.iter().all(is_opaque_solid)
+            });
+            report.duplicate_symbols = before - list.symbols.len();
+        }
+        if self.fold_transforms {
+            for symbol in &mut list.symbols {
+                report.folded_transforms += self.fold(symbol) as usize;
+            }
+        }
+        if self.merge_paths {
+            let before = list.symbols.len();
+            let tolerance = self.tolerance;
+            list.symbols.dedup_by(|b, a| {
+                if !can_merge(a, b) {
+                    return false;
+                }
+                let mut path: BezPath = a.shape.to_path(tolerance);
+                path.extend(b.shape.path_elements(tolerance));
+                a.shape = StaticShape::BezPath(path);
+                true
+            });
+            report.merged_symbols = before - list.symbols.len();
+        }
+        if self.remove_empty_layers {
+            report.empty_layers = remove_empty_layers(list);
+        }
+        list.invalidate_index();
+        report
+    }
+
+    /// Applies the transform of `symbol` to its shape, returning whether it did.
+    fn fold(&self, symbol: &mut Symbol) -> bool {
+        if symbol.brush_transform == Some(Affine::IDENTITY) {
+            symbol.brush_transform = None;
+        }
+        let transform = symbol.transform;
+        let [a, b, c, d, _, _] = transform.as_coeffs();
+        let translation = [a, b, c, d] == [1.0, 0.0, 0.0, 1.0];
+        if transform == Affine::IDENTITY
+            || symbol.paint_ops.iter().all(|op| matches!(op, PaintOp::PopLayer))
+            // Strokes would change width.
+            || (!translation
+                && symbol
+                    .paint_ops
+                    .iter()
+                    .any(|op| matches!(op, PaintOp::Stroke { .. })))
+        {
+            return false;
+        }
+        let Some(shape) = fold_shape(&symbol.shape, transform, self.tolerance) else {
+            return false;
+        };
+        // Brushes are positioned by the transform too, which solid colors don't care about.
+        let positioned = symbol.paint_ops.iter().any(|op| match op {
+            PaintOp::Fill { brush, .. } | PaintOp::Stroke { brush, .. } => {
+                !matches!(brush, Brush::Solid(_))
+            }
+            PaintOp::PushLayer { .. } | PaintOp::PopLayer => false,
+        });
+        symbol.brush_transform = match symbol.brush_transform {
+            Some(brush_transform) => Some(transform * brush_transform),
+            None if positioned => Some(transform),
+            None => None,
+        };
+        symbol.shape = shape;
+        symbol.transform = Affine::IDENTITY;
+        true
+    }
+}
+
+/// Removes the ops at `removed`, given as symbol and op indices, and any symbols left empty.
+fn remove_ops(list: &mut DisplayList, removed: &HashSet<(usize, usize)>) {
+    if removed.is_empty() {
+        return;
+    }
+    for (i, symbol) in list.symbols.iter_mut().enumerate() {
+        let mut j = 0;
+        symbol.paint_ops.retain(|_| {
+            j += 1;
+            !removed.contains(&(i, j - 1))
+        });
+    }
+    list.symbols.retain(|symbol| !symbol.paint_ops.is_empty());
+}
+
+fn remove_invisible(list: &mut DisplayList) -> usize {
+    let mut removed = HashSet::new();
+    // Whether each layer which hasn't been popped is being removed.
+    let mut layers: Vec<bool> = Vec::new();
+    for (i, symbol) in list.symbols.iter().enumerate() {
+        for (j, op) in symbol.paint_ops.iter().enumerate() {
+            let in_removed = layers.last().copied().unwrap_or(false);
+            let remove = match op {
+                PaintOp::PushLayer { alpha, .. } => {
+                    let remove = in_removed || *alpha == 0.0;
+                    layers.push(remove);
+                    remove
+                }
+                PaintOp::PopLayer => layers.pop().unwrap_or(false),
+                _ => in_removed || is_invisible(op, &symbol.shape),
+            };
+            if remove {
+                removed.insert((i, j));
+            }
+        }
+    }
+    remove_ops(list, &removed);
+    removed.len()
+}
+
+fn remove_empty_layers(list: &mut DisplayList) -> usize {
+    let mut removed = HashSet::new();
+    // The position of each push which hasn't been popped, and whether anything was painted.
+    let mut layers: Vec<((usize, usize), bool)> = Vec::new();
+    for (i, symbol) in list.symbols.iter().enumerate() {
+        for (j, op) in symbol.paint_ops.iter().enumerate() {
+            match op {
+                PaintOp::PushLayer { .. } => layers.push(((i, j), false)),
+                PaintOp::PopLayer => match layers.pop() {
+                    Some((push, false)) => {
+                        removed.insert(push);
+                        removed.insert((i, j));
+                    }
+                    Some((_, true)) => {
+                        if let Some((_, painted)) = layers.last_mut() {
+                            *painted = true;
+                        }
+                    }
+                    None => (),
+                },
+                _ => {
+                    if let Some((_, painted)) = layers.last_mut() {
+                        *painted = true;
+                    }
+                }
+            }
+        }
+    }
+    remove_ops(list, &removed);
+    removed.len() / 2
+}
+
+impl DisplayList {
+    /// Runs the default [`Optimizer`] passes.
+    pub fn optimize(&mut self) -> OptimizeReport {
+        Optimizer::default().optimize(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fill_op, stroke_op, symbol};
+    use crate::StrokeAlignment;
+    use peniko::{BlendMode, Color};
+
+    #[test]
+    fn optimize_passes() {
+        let at = |x: f64| Affine::translate((x, 0.0));
+        let stroke = stroke_op(1.0, Color::BLACK, StrokeAlignment::Center);
+        let push = PaintOp::PushLayer {
+            blend: BlendMode::default(),
+            alpha: 0.5,
+        };
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let mut list = DisplayList::new();
+        list.push(symbol(square, vec![fill_op(Color::TRANSPARENT)], at(0.0)));
+        list.push(symbol(square, vec![stroke.clone()], at(0.0)));
+        list.push(symbol(square, vec![stroke.clone()], at(0.0)));
+        list.push(symbol(square, vec![stroke.clone()], at(5.0)));
+        list.push(symbol(square, vec![push], at(0.0)));
+        let line = Rect::new(0.0, 0.0, 10.0, 0.0);
+        list.push(symbol(line, vec![fill_op(Color::RED)], at(0.0)));
+        list.push(symbol(Rect::ZERO, vec![PaintOp::PopLayer], at(0.0)));
+        let report = list.optimize();
+        assert_eq!(
+            report,
+            OptimizeReport {
+                invisible_ops: 2,
+                duplicate_symbols: 1,
+                folded_transforms: 1,
+                merged_symbols: 1,
+                empty_layers: 1,
+            }
+        );
+        assert_eq!(list.symbols.len(), 1);
+        assert_eq!(list.symbols[0].paint_ops, vec![stroke]);
+        assert_eq!(list.symbols[0].transform, Affine::IDENTITY);
+        assert!(matches!(list.symbols[0].shape, StaticShape::BezPath(_)));
+    }
+}
@@ -0,0 +1,989 @@
+use crate::icc::{RgbSpace, DISPLAY_P3, SRGB};
+use crate::metadata::Metadata;
+use crate::offset::StrokeAlignment;
+use crate::pdf_whisperer::PdfAViolation;
+use crate::print_color::{PrintColor, ProcessColor};
+use crate::whisperer::*;
+use kurbo::{Affine, Shape};
+use pdf_writer::types::{ColorSpaceOperand, OverprintMode};
+use pdf_writer::{Chunk, Content, Name, Ref, TextStr};
+
+/// Writes ops into a PDF content stream, keeping track of the resources it needs.
+///
+/// Ops are painted in the coordinate space the content stream is in, so a drawing with y
+/// pointing down should be flipped first with [`PdfContent::content_mut`], or embedded
+/// with [`PdfContent::write_form`] which does that itself. Gradient and image brushes
+/// aren't supported yet, ops using them are painted in the current color.
+///
+/// Print colors from metadata are painted as DeviceCMYK colors and Separation color spaces,
+/// in place of the brushes of solid fills and strokes. RGB colors are tagged as sRGB with
+/// an ICC profile in the `DefaultRGB` color space, and Display P3 colors are painted in an
+/// ICC based color space of their own.
+pub struct PdfContent {
+    content: Content,
+    tolerance: f64,
+    // The open layers, innermost last.
+    layers: Vec<Layer>,
+    // The content of layers painted as transparency groups, in Form XObjects named
+    // `Gr{index}`. Their index is taken when pushed, so it is `None` while they are open.
+    groups: Vec<Option<TransparencyGroup>>,
+    // The first key of the group forms in the structure parent tree, when the ops in them
+    // are tagged.
+    pub(crate) group_struct_parents: Option<i32>,
+    // Distinct graphics states used, named `GS{index}`.
+    ext_g_states: Vec<ExtGState>,
+    // Distinct spot colors used with their alternates, in color spaces named `CS{index}`.
+    separations: Vec<(String, ProcessColor)>,
+    // The print colors of the ops being painted.
+    print: PrintPaint,
+    // The names of the viewer layers used, with optional content groups named `OC{index}`.
+    optional_content: Vec<String>,
+    // The viewer layer the ops being painted are on.
+    viewer_layer: Option<usize>,
+    // Whether DeviceRGB colors, which are tagged as sRGB, are used.
+    srgb: bool,
+    // Whether Display P3 colors are used, in the color space named `P3`.
+    display_p3: bool,
+    // The first op which couldn't be painted faithfully.
+    pub(crate) violation: Option<PdfAViolation>,
+}
+
+struct Layer {
+    // Whether an optional content sequence ends along with it.
+    marked: bool,
+    // The content the layer is painted into as a transparency group, when it has an alpha
+    // or blend mode.
+    group: Option<OpenGroup>,
+}
+
+struct OpenGroup {
+    index: usize,
+    // The content the group is painted into when it is popped.
+    parent: Content,
+    state: ExtGState,
+    bbox: kurbo::Rect,
+}
+
+struct TransparencyGroup {
+    content: Vec<u8>,
+    bbox: kurbo::Rect,
+}
+
+/// A graphics state set with `gs`.
+#[derive(Clone, Copy, PartialEq)]
+enum ExtGState {
+    Layer { alpha: f32, mix: peniko::Mix },
+    Overprint,
+}
+
+/// Print colors from metadata.
+#[derive(Clone, Default)]
+struct PrintPaint {
+    fill: Option<PrintColor>,
+    stroke: Option<PrintColor>,
+    overprint: bool,
+}
+
+impl PrintPaint {
+    fn new(metadata: &Metadata) -> Self {
+        Self {
+            fill: metadata.fill_color.clone(),
+            stroke: metadata.stroke_color.clone(),
+            overprint: metadata.overprint,
+        }
+    }
+}
+
+/// The named resources used by a [`PdfContent`], which need to be added to the resource
+/// dictionary of the page or XObject the content ends up in.
+#[derive(Clone, Debug, Default)]
+pub struct PdfResources {
+    ext_g_states: Vec<(String, Ref)>,
+    color_spaces: Vec<(String, Ref)>,
+    // The viewer layer of each optional content group.
+    optional_content: Vec<(String, Ref)>,
+    x_objects: Vec<(String, Ref)>,
+    // The sRGB profile, which the document can share.
+    pub(crate) srgb_profile: Option<Ref>,
+}
+
+impl PdfResources {
+    pub fn write(&self, resources: &mut pdf_writer::writers::Resources<'_>) {
+        resources.ext_g_states().pairs(
+            self.ext_g_states
+                .iter()
+                .map(|(name, id)| (Name(name.as_bytes()), *id)),
+        );
+        if !self.color_spaces.is_empty() {
+            resources.color_spaces().pairs(
+                self.color_spaces
+                    .iter()
+                    .map(|(name, id)| (Name(name.as_bytes()), *id)),
+            );
+        }
+        if !self.x_objects.is_empty() {
+            resources.x_objects().pairs(
+                self.x_objects
+                    .iter()
+                    .map(|(name, id)| (Name(name.as_bytes()), *id)),
+            );
+        }
+        if !self.optional_content.is_empty() {
+            let mut properties = resources.insert(Name(b"Properties")).dict();
+            for (i, (_, id)) in self.optional_content.iter().enumerate() {
+                properties.pair(Name(optional_content_name(i).as_bytes()), *id);
+            }
+        }
+    }
+
+    /// Adds a color space written elsewhere under `name`.
+    pub(crate) fn push_color_space(&mut self, name: &str, id: Ref) {
+        self.color_spaces.push((name.to_owned(), id));
+    }
+
+    /// The optional content group written for each viewer layer painted on, which need to
+    /// be listed in the `OCProperties` of the document catalog.
+    pub fn optional_content_groups(&self) -> impl Iterator<Item = (&str, Ref)> {
+        self.optional_content
+            .iter()
+            .map(|(layer, id)| (layer.as_str(), *id))
+    }
+
+    /// The Form XObject of each transparency group, by the index from
+    /// [`PdfContent::group`].
+    pub(crate) fn groups(&self) -> impl Iterator<Item = Ref> + '_ {
+        self.x_objects.iter().map(|&(_, id)| id)
+    }
+}
+
+impl PdfContent {
+    pub fn new(tolerance: f64) -> Self {
+        Self::with_content(Content::new(), tolerance)
+    }
+
+    /// Continues painting into `content`, which may already have operators in it.
+    ///
+    /// Resource names start with `GS`, `CS`, `OC` or `Gr`, or are `DefaultRGB` and `P3`, so
+    /// they shouldn't be used by the caller.
+    pub fn with_content(content: Content, tolerance: f64) -> Self {
+        Self {
+            content,
+            tolerance,
+            layers: Vec::new(),
+            groups: Vec::new(),
+            group_struct_parents: None,
+            ext_g_states: Vec::new(),
+            separations: Vec::new(),
+            print: PrintPaint::default(),
+            optional_content: Vec::new(),
+            viewer_layer: None,
+            srgb: false,
+            display_p3: false,
+            violation: None,
+        }
+    }
+
+    /// Puts the ops painted from now on, and the contents of the layers they push, on the
+    /// viewer layer `name` until it is set again.
+    ///
+    /// Each viewer layer gets an optional content group, see
+    /// [`PdfResources::optional_content_groups`].
+    pub fn set_viewer_layer(&mut self, name: Option<&str>) {
+        self.viewer_layer =
+            name.map(
+                |name| match self.optional_content.iter().position(|x| x == name) {
+                    Some(i) => i,
+                    None => {
+                        self.optional_content.push(name.to_owned());
+                        self.optional_content.len() - 1
+                    }
+                },
+            );
+    }
+
+    /// Paints the ops painted from now on in the print colors of `metadata`, if any.
+    pub(crate) fn set_print_colors(&mut self, metadata: Option<&Metadata>) {
+        self.print = metadata.map(PrintPaint::new).unwrap_or_default();
+    }
+
+    /// Begins an optional content sequence for the current viewer layer, if there is one.
+    fn begin_viewer_layer(&mut self) -> bool {
+        let Some(i) = self.viewer_layer else {
+            return false;
+        };
+        self.content
+            .begin_marked_content_with_properties(Name(b"OC"))
+            .properties_named(Name(optional_content_name(i).as_bytes()));
+        true
+    }
+
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    /// The content stream, for adding operators between ops.
+    ///
+    /// Inside a layer with an alpha or blend mode, this is the content of its transparency
+    /// group.
+    pub fn content_mut(&mut self) -> &mut Content {
+        &mut self.content
+    }
+
+    /// The index of the transparency group ops are painted into, if any.
+    pub(crate) fn group(&self) -> Option<usize> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| Some(layer.group.as_ref()?.index))
+    }
+
+    /// Writes the objects of the resources used so far to `chunk`, with ids from `alloc`
+    /// and streams compressed at `compression_level` as in [`PdfOptions`](crate::PdfOptions).
+    ///
+    /// Open layers are closed first, since their transparency groups are among the resources.
+    pub fn write_resources(
+        &mut self,
+        chunk: &mut Chunk,
+        compression_level: u8,
+        mut alloc: impl FnMut() -> Ref,
+    ) -> PdfResources {
+        while !self.layers.is_empty() {
+            self.pop_layer();
+        }
+        let mut resources = PdfResources::default();
+        for (i, state) in self.ext_g_states.iter().enumerate() {
+            let id = alloc();
+            let mut writer = chunk.ext_graphics(id);
+            match *state {
+                ExtGState::Layer { alpha, mix } => {
+                    writer.stroking_alpha(alpha).non_stroking_alpha(alpha);
+                    if let Some(mode) = blend_mode(mix) {
+                        writer.blend_mode(mode);
+                    }
+                }
+                ExtGState::Overprint => {
+                    writer
+                        .overprint(true)
+                        .overprint_fill(true)
+                        .overprint_mode(OverprintMode::IgnoreZeroChannel);
+                }
+            }
+            resources.ext_g_states.push((ext_g_state_name(i), id));
+        }
+        let uses_rgb =
+            self.srgb || (self.separations.iter()).any(|(_, x)| matches!(x, ProcessColor::Rgb(_)));
+        let uses_p3 = self.display_p3
+            || (self.separations.iter()).any(|(_, x)| matches!(x, ProcessColor::DisplayP3(_)));
+        let mut icc_based = |chunk: &mut Chunk, name: &str, space: &RgbSpace| {
+            let profile_id = alloc();
+            write_profile(chunk, profile_id, space, compression_level);
+            let id = alloc();
+            chunk.color_space(id).icc_based(profile_id);
+            resources.color_spaces.push((name.to_owned(), id));
+            profile_id
+        };
+        if uses_rgb {
+            resources.srgb_profile = Some(icc_based(chunk, "DefaultRGB", &SRGB));
+        }
+        let p3_profile = uses_p3.then(|| icc_based(chunk, DISPLAY_P3_NAME, &DISPLAY_P3));
+        for (i, (name, alternate)) in self.separations.iter().enumerate() {
+            let id = alloc();
+            let mut separation = chunk.color_space(id).separation(Name(name.as_bytes()));
+            let space = separation.alternate_color_space();
+            match alternate {
+                ProcessColor::Rgb(_) => space.device_rgb(),
+                ProcessColor::Cmyk(_) => space.device_cmyk(),
+                ProcessColor::DisplayP3(_) => space.icc_based(p3_profile.unwrap()),
+            }
+            separation
+                .tint_exponential()
+                .domain([0.0, 1.0])
+                .c0(components(alternate.tinted(0.0)))
+                .c1(components(*alternate))
+                .n(1.0);
+            drop(separation);
+            resources.color_spaces.push((color_space_name(i), id));
+        }
+        for layer in &self.optional_content {
+            let id = alloc();
+            chunk
+                .indirect(id)
+                .dict()
+                .pair(Name(b"Type"), Name(b"OCG"))
+                .pair(Name(b"Name"), TextStr(layer));
+            resources.optional_content.push((layer.clone(), id));
+        }
+        // Groups share a dictionary with the resources of the content, including each other.
+        let groups = self.groups.iter().flatten();
+        resources.x_objects = (groups.clone().enumerate())
+            .map(|(i, _)| (group_name(i), alloc()))
+            .collect();
+        let shared_id = (!resources.x_objects.is_empty()).then(&mut alloc);
+        if let Some(id) = shared_id {
+            resources.write(&mut chunk.indirect(id).start());
+        }
+        for (i, group) in groups.enumerate() {
+            let (data, filter) = compress(&group.content, compression_level);
+            let mut form = chunk.form_xobject(resources.x_objects[i].1, &data);
+            form.bbox(pdf_rect(group.bbox));
+            form.group().transparency();
+            if let Some(key) = self.group_struct_parents {
+                form.struct_parents(key + i as i32);
+            }
+            if let Some(filter) = filter {
+                form.filter(filter);
+            }
+            form.pair(Name(b"Resources"), shared_id.unwrap());
+        }
+        resources
+    }
+
+    /// Closes open layers and returns the content stream.
+    pub fn finish(mut self) -> Content {
+        while !self.layers.is_empty() {
+            self.pop_layer();
+        }
+        self.content
+    }
+
+    /// Writes the content as a Form XObject `id` to `chunk`, along with its resources
+    /// using ids from `alloc`, and compressed at `compression_level` as in
+    /// [`PdfOptions`](crate::PdfOptions).
+    ///
+    /// The form covers `size` with y pointing down like the drawing, and shows it upright
+    /// at the origin of the coordinate space it is painted in with the `Do` operator.
+    pub fn write_form(
+        mut self,
+        chunk: &mut Chunk,
+        id: Ref,
+        size: kurbo::Size,
+        compression_level: u8,
+        alloc: impl FnMut() -> Ref,
+    ) -> PdfResources {
+        let resources = self.write_resources(chunk, compression_level, alloc);
+        let (data, filter) = compress(&self.finish().finish(), compression_level);
+        let mut form = chunk.form_xobject(id, &data);
+        form.bbox(pdf_writer::Rect::new(
+            0.0,
+            0.0,
+            size.width as f32,
+            size.height as f32,
+        ))
+        .matrix([1.0, 0.0, 0.0, -1.0, 0.0, size.height as f32]);
+        if let Some(filter) = filter {
+            form.filter(filter);
+        }
+        resources.write(&mut form.resources());
+        resources
+    }
+}
+
+/// Compresses stream data at `level`, returning it along with the filter to decode it.
+pub(crate) fn compress(data: &[u8], level: u8) -> (Vec<u8>, Option<pdf_writer::Filter>) {
+    if level == 0 {
+        (data.to_vec(), None)
+    } else {
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(data, level.min(10));
+        (data, Some(pdf_writer::Filter::FlateDecode))
+    }
+}
+
+/// Writes the ICC profile of `space` as the stream `id`.
+pub(crate) fn write_profile(chunk: &mut Chunk, id: Ref, space: &RgbSpace, level: u8) {
+    let (profile, filter) = compress(&space.profile(), level);
+    let mut profile = chunk.icc_profile(id, &profile);
+    profile.n(3);
+    if let Some(filter) = filter {
+        profile.filter(filter);
+    }
+}
+
+const DISPLAY_P3_NAME: &str = "P3";
+
+fn ext_g_state_name(i: usize) -> String {
+    format!("GS{i}")
+}
+
+fn components(color: ProcessColor) -> Vec<f32> {
+    match color {
+        ProcessColor::Rgb(rgb) | ProcessColor::DisplayP3(rgb) => rgb.to_vec(),
+        ProcessColor::Cmyk(cmyk) => cmyk.to_vec(),
+    }
+}
+
+fn group_name(i: usize) -> String {
+    format!("Gr{i}")
+}
+
+fn pdf_rect(r: kurbo::Rect) -> pdf_writer::Rect {
+    pdf_writer::Rect::new(r.x0 as f32, r.y0 as f32, r.x1 as f32, r.y1 as f32)
+}
+
+fn color_space_name(i: usize) -> String {
+    format!("CS{i}")
+}
+
+fn optional_content_name(i: usize) -> String {
+    format!("OC{i}")
+}
+
+fn blend_mode(mix: peniko::Mix) -> Option<pdf_writer::types::BlendMode> {
+    use pdf_writer::types::BlendMode as B;
+    use peniko::Mix;
+    Some(match mix {
+        Mix::Normal | Mix::Clip => return None,
+        Mix::Multiply => B::Multiply,
+        Mix::Screen => B::Screen,
+        Mix::Overlay => B::Overlay,
+        Mix::Darken => B::Darken,
+        Mix::Lighten => B::Lighten,
+        Mix::ColorDodge => B::ColorDodge,
+        Mix::ColorBurn => B::ColorBurn,
+        Mix::HardLight => B::HardLight,
+        Mix::SoftLight => B::SoftLight,
+        Mix::Difference => B::Difference,
+        Mix::Exclusion => B::Exclusion,
+        Mix::Hue => B::Hue,
+        Mix::Saturation => B::Saturation,
+        Mix::Color => B::Color,
+        Mix::Luminosity => B::Luminosity,
+    })
+}
+
+impl PdfContent {
+    /// Pushes a layer clipped to `shape`. With an alpha or blend mode, the layer is painted
+    /// into a transparency group, which is composited as a whole when popped.
+    pub(crate) fn push_layer(
+        &mut self,
+        blend: peniko::BlendMode,
+        alpha: f32,
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        let marked = self.begin_viewer_layer();
+        self.content.save_state();
+        let clip = transform * shape.to_path(self.tolerance);
+        push_path(&mut self.content, &clip, self.tolerance);
+        self.content.clip_nonzero();
+        self.content.end_path();
+        let mix = blend.mix;
+        let group = (alpha != 1.0 || blend_mode(mix).is_some()).then(|| {
+            self.groups.push(None);
+            OpenGroup {
+                index: self.groups.len() - 1,
+                parent: std::mem::replace(&mut self.content, Content::new()),
+                state: ExtGState::Layer { alpha, mix },
+                bbox: clip.bounding_box(),
+            }
+        });
+        self.layers.push(Layer { marked, group });
+    }
+
+    /// Sets the graphics state `state`, sharing it with earlier uses.
+    fn set_ext_g_state(&mut self, state: ExtGState) {
+        let i = match self.ext_g_states.iter().position(|x| *x == state) {
+            Some(i) => i,
+            None => {
+                self.ext_g_states.push(state);
+                self.ext_g_states.len() - 1
+            }
+        };
+        self.content
+            .set_parameters(Name(ext_g_state_name(i).as_bytes()));
+    }
+
+    pub(crate) fn pop_layer(&mut self) {
+        let Some(layer) = self.layers.pop() else {
+            return;
+        };
+        if let Some(group) = layer.group {
+            let content = std::mem::replace(&mut self.content, group.parent);
+            self.groups[group.index] = Some(TransparencyGroup {
+                content: content.finish(),
+                bbox: group.bbox,
+            });
+            self.set_ext_g_state(group.state);
+            self.content
+                .x_object(Name(group_name(group.index).as_bytes()));
+        }
+        self.content.restore_state();
+        if layer.marked {
+            self.content.end_marked_content();
+        }
+    }
+}
+
+pub(crate) fn array_magic<T, U, const SZ: usize, F: Fn(T) -> U>(src: [T; SZ], f: F) -> [U; SZ]
+where
+    T: Copy,
+{
+    use std::mem::MaybeUninit;
+
+    let dest: [U; SZ] = unsafe {
+        let mut dest = MaybeUninit::uninit();
+        // safety, for i in 0..SZ dest[i] = f(x) initializing the entire range.
+        for (i, &x) in src.iter().enumerate() {
+            (dest.as_mut_ptr() as *mut U).add(i).write(f(x));
+        }
+        dest.assume_init()
+    };
+
+    dest
+}
+
+/// Appends the path of `shape` to `content`.
+pub(crate) fn push_path(content: &mut Content, shape: &impl Shape, tolerance: f64) {
+    if let Some(line) = shape.as_line() {
+        content.move_to(line.p0.x as f32, line.p0.y as f32);
+        content.line_to(line.p1.x as f32, line.p1.y as f32);
+    } else if let Some(r) = shape.as_rect() {
+        content.rect(
+            r.x0 as f32,
+            r.y0 as f32,
+            r.width() as f32,
+            r.height() as f32,
+        );
+    } else {
+        let path = shape.into_path(tolerance);
+        // PDF has no quadratic curves, they are raised to cubics from the current point.
+        let (mut start, mut last) = (kurbo::Point::ZERO, kurbo::Point::ZERO);
+        for elem in path {
+            match elem {
+                kurbo::PathEl::MoveTo(pt) => {
+                    content.move_to(pt.x as f32, pt.y as f32);
+                    (start, last) = (pt, pt);
+                }
+                kurbo::PathEl::LineTo(pt) => {
+                    content.line_to(pt.x as f32, pt.y as f32);
+                    last = pt;
+                }
+                kurbo::PathEl::QuadTo(p1, p2) => {
+                    let a = last.lerp(p1, 2.0 / 3.0);
+                    let b = p2.lerp(p1, 2.0 / 3.0);
+                    content.cubic_to(
+                        a.x as f32,
+                        a.y as f32,
+                        b.x as f32,
+                        b.y as f32,
+                        p2.x as f32,
+                        p2.y as f32,
+                    );
+                    last = p2;
+                }
+                kurbo::PathEl::CurveTo(a, b, c) => {
+                    content.cubic_to(
+                        a.x as f32, a.y as f32, b.x as f32, b.y as f32, c.x as f32, c.y as f32,
+                    );
+                    last = c;
+                }
+                kurbo::PathEl::ClosePath => {
+                    content.close_path();
+                    last = start;
+                }
+            }
+        }
+    }
+}
+
+impl PdfContent {
+    fn set_brush(&mut self, brush: peniko::BrushRef<'_>, stroking: bool) {
+        let print = match stroking {
+            true => &self.print.stroke,
+            false => &self.print.fill,
+        };
+        if let (peniko::BrushRef::Solid(_), Some(color)) = (brush, print.clone()) {
+            return self.set_print_color(&color, stroking);
+        }
+        match brush {
+            peniko::BrushRef::Solid(x) => {
+                self.set_rgb([x.r, x.g, x.b].map(|c| c as f32 / 255.0), stroking);
+            }
+            peniko::BrushRef::Gradient(_x) => {
+                self.violation.get_or_insert(PdfAViolation::Gradient);
+            }
+            peniko::BrushRef::Image(_x) => {
+                self.violation.get_or_insert(PdfAViolation::Image);
+            }
+        }
+    }
+
+    /// Sets a DeviceRGB color, which is tagged as sRGB.
+    fn set_rgb(&mut self, [r, g, b]: [f32; 3], stroking: bool) {
+        self.srgb = true;
+        if stroking {
+            self.content.set_stroke_rgb(r, g, b);
+        } else {
+            self.content.set_fill_rgb(r, g, b);
+        }
+    }
+
+    /// Sets a color in the color space resource `name`.
+    fn set_named_color(&mut self, name: &str, components: &[f32], stroking: bool) {
+        let space = ColorSpaceOperand::Named(Name(name.as_bytes()));
+        let components = components.iter().copied();
+        if stroking {
+            self.content.set_stroke_color_space(space);
+            self.content.set_stroke_color(components);
+        } else {
+            self.content.set_fill_color_space(space);
+            self.content.set_fill_color(components);
+        }
+    }
+
+    fn set_print_color(&mut self, color: &PrintColor, stroking: bool) {
+        match color {
+            PrintColor::Process(ProcessColor::Rgb(rgb)) => self.set_rgb(*rgb, stroking),
+            PrintColor::Process(ProcessColor::DisplayP3(rgb)) => {
+                self.display_p3 = true;
+                self.set_named_color(DISPLAY_P3_NAME, rgb, stroking);
+            }
+            PrintColor::Process(ProcessColor::Cmyk([c, m, y, k])) => {
+                self.violation.get_or_insert(PdfAViolation::Cmyk);
+                if stroking {
+                    self.content.set_stroke_cmyk(*c, *m, *y, *k);
+                } else {
+                    self.content.set_fill_cmyk(*c, *m, *y, *k);
+                }
+            }
+            PrintColor::Spot {
+                name,
+                tint,
+                alternate,
+            } => {
+                if matches!(alternate, ProcessColor::Cmyk(_)) {
+                    self.violation.get_or_insert(PdfAViolation::Cmyk);
+                }
+                let separation = (name.clone(), *alternate);
+                let i = match self.separations.iter().position(|x| *x == separation) {
+                    Some(i) => i,
+                    None => {
+                        self.separations.push(separation);
+                        self.separations.len() - 1
+                    }
+                };
+                self.set_named_color(&color_space_name(i), &[*tint], stroking);
+            }
+        }
+    }
+
+    /// Sets the graphics state for the print colors, at the start of painting an op.
+    fn set_print_state(&mut self) {
+        if self.print.overprint {
+            self.set_ext_g_state(ExtGState::Overprint);
+        }
+    }
+
+    fn set_stroke_style(&mut self, style: &kurbo::Stroke, width: f64) {
+        self.content.set_line_join(match style.join {
+            kurbo::Join::Bevel => pdf_writer::types::LineJoinStyle::BevelJoin,
+            kurbo::Join::Round => pdf_writer::types::LineJoinStyle::RoundJoin,
+            kurbo::Join::Miter => pdf_writer::types::LineJoinStyle::MiterJoin,
+        });
+        self.content.set_line_width(width as f32);
+        self.content.set_miter_limit(style.miter_limit as f32);
+    }
+
+    /// Paints a fill and a centered stroke of the same path with a single `B` or `B*`.
+    fn fill_and_stroke(
+        &mut self,
+        fill: peniko::Fill,
+        fill_brush: peniko::BrushRef<'_>,
+        style: &kurbo::Stroke,
+        stroke_brush: peniko::BrushRef<'_>,
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        self.content.save_state();
+        self.set_print_state();
+        self.content
+            .transform(array_magic(transform.as_coeffs(), |x| x as f32));
+        push_path(&mut self.content, shape, self.tolerance);
+        self.set_brush(fill_brush, false);
+        self.set_brush(stroke_brush, true);
+        self.set_stroke_style(style, style.width);
+        match fill {
+            peniko::Fill::EvenOdd => self.content.fill_even_odd_and_stroke(),
+            peniko::Fill::NonZero => self.content.fill_nonzero_and_stroke(),
+        };
+        self.content.restore_state();
+    }
+}
+
+impl PdfContent {
+    fn paint_op(&mut self, op: PaintOpRef<'_, '_>, transform: Affine, shape: &impl Shape) {
+        match op {
+            PaintOpRef::PushLayer { blend, alpha } => {
+                return self.push_layer(blend, alpha, transform, shape);
+            }
+            PaintOpRef::PopLayer => return self.pop_layer(),
+            _ => (),
+        }
+        self.content.save_state();
+        self.set_print_state();
+        self.content
+            .transform(array_magic(transform.as_coeffs(), |x| x as f32));
+        push_path(&mut self.content, shape, self.tolerance);
+        match op {
+            PaintOpRef::Fill { style, brush } => {
+                self.set_brush(brush, false);
+                match style {
+                    peniko::Fill::EvenOdd => {
+                        self.content.fill_even_odd();
+                    }
+                    peniko::Fill::NonZero => {
+                        self.content.fill_nonzero();
+                    }
+                }
+            }
+            PaintOpRef::Stroke {
+                style,
+                brush,
+                alignment,
+            } => {
+                self.set_brush(brush, true);
+                let width = match alignment {
+                    StrokeAlignment::Center => style.width,
+                    // Clip to the inside or outside of the shape and stroke twice as wide,
+                    // the path is still current so clip and redraw it.
+                    StrokeAlignment::Inside => {
+                        self.content.clip_nonzero();
+                        self.content.end_path();
+                        push_path(&mut self.content, shape, self.tolerance);
+                        style.width * 2.0
+                    }
+                    // The current path gets a frame around it so the even-odd rule clips
+                    // to the outside.
+                    StrokeAlignment::Outside => {
+                        let grow = style.width * style.miter_limit.max(1.0);
+                        let outer = shape.bounding_box().inflate(grow, grow);
+                        push_path(&mut self.content, &outer, self.tolerance);
+                        self.content.clip_even_odd();
+                        self.content.end_path();
+                        push_path(&mut self.content, shape, self.tolerance);
+                        style.width * 2.0
+                    }
+                };
+                self.set_stroke_style(style, width);
+                self.content.stroke();
+            }
+            PaintOpRef::PushLayer { .. } | PaintOpRef::PopLayer => unreachable!(),
+        }
+        self.content.restore_state();
+    }
+
+    /// A fill followed by a centered stroke is painted with a single fill and stroke operator.
+    pub(crate) fn paint_ops<'a, 'b>(
+        &mut self,
+        ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        // Whether an optional content sequence is open for the ops painted so far.
+        let mut marked = false;
+        let mut ops = ops.into_iter().peekable();
+        while let Some(op) = ops.next() {
+            let layer_op = matches!(op, PaintOpRef::PushLayer { .. } | PaintOpRef::PopLayer);
+            if marked && layer_op {
+                self.content.end_marked_content();
+                marked = false;
+            } else if !marked && !layer_op {
+                marked = self.begin_viewer_layer();
+            }
+            if let PaintOpRef::Fill {
+                style: fill,
+                brush: fill_brush,
+            } = op
+            {
+                if let Some(PaintOpRef::Stroke {
+                    style,
+                    brush,
+                    alignment: StrokeAlignment::Center,
+                }) = ops.peek().copied()
+                {
+                    ops.next();
+                    self.fill_and_stroke(fill, fill_brush, style, brush, transform, shape);
+                    continue;
+                }
+            }
+            self.paint_op(op, transform, shape)
+        }
+        if marked {
+            self.content.end_marked_content();
+        }
+    }
+}
+
+impl SceneWhisperer for PdfContent {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.paint_ops([op], transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.paint_ops(ops, transform, shape);
+    }
+
+    /// Ops on a viewer layer are marked as optional content, and print colors are used.
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        let outer = self.viewer_layer;
+        if let Some(layer) = &metadata.layer {
+            self.set_viewer_layer(Some(layer));
+        }
+        self.set_print_colors(Some(metadata));
+        self.paint_ops(ops, transform, shape);
+        self.set_print_colors(None);
+        self.viewer_layer = outer;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_contains, fill, push, stroke, uncompressed, written};
+    use crate::{Pdf, PdfOptions};
+    use kurbo::{BezPath, Rect, Size, Stroke};
+    use peniko::{Color, Fill};
+
+    #[test]
+    fn pdf_transparency_groups() {
+        let options = PdfOptions {
+            tagged: true,
+            ..uncompressed()
+        };
+        let mut pdf = Pdf::with_options(Size::new(20.0, 20.0), 0.1, options);
+        let fill = fill(Color::BLACK);
+        let page = Rect::new(0.0, 0.0, 20.0, 20.0);
+        // Overlapping squares in a half transparent layer are composited as a whole, and
+        // a layer without alpha is just clipped.
+        pdf.apply_paint_ops([push(0.5), push(1.0)], Affine::IDENTITY, None, &page);
+        let described = Metadata::new().with_description("A square");
+        for square in [
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(5.0, 5.0, 15.0, 15.0),
+        ] {
+            pdf.apply_paint_ops_with_metadata(&described, [fill], Affine::IDENTITY, None, &square);
+        }
+        let pop = PaintOpRef::PopLayer;
+        pdf.apply_paint_ops([pop, pop], Affine::IDENTITY, None, &page);
+        let ops = [push(0.5), push(0.5), fill];
+        pdf.apply_paint_ops(ops, Affine::IDENTITY, None, &page);
+        // The last layer is left open and closed when written.
+        let out = written(pdf);
+        assert_contains(
+            &out,
+            &[
+                "/Gr0 Do",
+                "/Gr1 Do",
+                "/Gr2 Do",
+                "/StructParents 1",
+                "/Type /MCR",
+                "/Stm ",
+            ],
+        );
+        // Nested groups composite with their own alpha, which isn't multiplied.
+        assert_eq!(out.matches("/CA 0.5").count(), 1);
+        assert!(!out.contains("/CA 0.25"));
+        assert_eq!(out.matches("/S /Transparency").count(), 3);
+        assert_eq!(out.matches("/Stm 8 0 R").count(), 2);
+    }
+
+    #[test]
+    fn fill_and_stroke_merge() {
+        let path = BezPath::from_svg("M0 0L10 0L10 10Z").unwrap();
+        let style = Stroke::new(1.0);
+        let fill = |style| PaintOpRef::Fill {
+            style,
+            brush: (&Color::BLACK).into(),
+        };
+        let stroke = |alignment| stroke(&style, Color::RED, alignment);
+        // The content with `ops` applied at once, and one by one at different transforms.
+        let content = |ops: &[PaintOpRef<'_, '_>]| {
+            let mut pdf = PdfContent::new(0.1);
+            for (i, op) in ops.iter().enumerate() {
+                let transform = Affine::translate((i as f64 * 5.0, 0.0));
+                pdf.apply_paint_op(*op, transform, None, &path);
+            }
+            let mut merged = PdfContent::new(0.1);
+            merged.apply_paint_ops(ops.iter().copied(), Affine::IDENTITY, None, &path);
+            let text = |pdf: PdfContent| String::from_utf8(pdf.finish().finish()).unwrap();
+            (text(merged), text(pdf))
+        };
+        let operators = |text: &str| -> Vec<String> {
+            text.lines()
+                .filter_map(|line| line.rsplit(' ').next())
+                .filter(|op| ["f", "f*", "S", "B", "B*"].contains(op))
+                .map(String::from)
+                .collect()
+        };
+        let (merged, separate) = content(&[fill(Fill::NonZero), stroke(StrokeAlignment::Center)]);
+        assert_eq!(operators(&merged), ["B"]);
+        assert_eq!(operators(&separate), ["f", "S"]);
+        let (merged, _) = content(&[fill(Fill::EvenOdd), stroke(StrokeAlignment::Center)]);
+        assert_eq!(operators(&merged), ["B*"]);
+        for alignment in [StrokeAlignment::Inside, StrokeAlignment::Outside] {
+            let (merged, _) = content(&[fill(Fill::NonZero), stroke(alignment)]);
+            assert!(
+                !operators(&merged).iter().any(|op| op.starts_with('B')),
+                "{alignment:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn pdf_form_xobject() {
+        let mut form = PdfContent::new(0.1);
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let push = PaintOpRef::PushLayer {
+            blend: peniko::Mix::Multiply.into(),
+            alpha: 0.5,
+        };
+        let ops = [push, fill(Color::BLACK), PaintOpRef::PopLayer];
+        form.apply_paint_ops(ops, Affine::IDENTITY, None, &square);
+
+        let mut pdf = pdf_writer::Pdf::new();
+        let mut next_id = Ref::new(10);
+        let form_id = next_id.bump();
+        let resources = form.write_form(&mut pdf, form_id, Size::new(10.0, 10.0), 0, || {
+            next_id.bump()
+        });
+        let mut page = Content::new();
+        page.x_object(Name(b"Fm0"));
+        pdf.stream(next_id.bump(), &page.finish());
+        resources.write(&mut pdf.indirect(next_id.bump()).start());
+        let out = pdf.finish();
+        assert_contains(
+            &String::from_utf8_lossy(&out),
+            &[
+                "/Subtype /Form",
+                "/BBox [0 0 10 10]",
+                "/Matrix [1 0 0 -1 0 10]",
+                "/GS0 11 0 R",
+                "/BM /Multiply",
+                "/GS0 gs",
+                "/Fm0 Do",
+            ],
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::pdf_content::push_path;
+use kurbo::{Circle, Line, Rect, Size};
+use pdf_writer::{Chunk, Content, Name, Ref};
+
+/// The narrowest slug marks are drawn in, half an inch.
+const MIN_SLUG: f64 = 36.0;
+
+/// The name of the registration color space in the resources of a page with marks.
+pub(crate) const REGISTRATION: &str = "Registration";
+
+/// Page boxes and printer's marks for sending a [`Pdf`](crate::Pdf) to a commercial printer.
+///
+/// The size the document is created with becomes its trim size, with the drawing extending
+/// `bleed` beyond it on every side and marks in `slug` around that. The page boxes are
+/// written so that printers know where to cut.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrintSetup {
+    /// How far beyond the trim on each side the drawing is printed, it should paint at
+    /// least that far past its edges.
+    pub bleed: f64,
+    /// The width of the area around the bleed for marks, which is at least half an inch
+    /// when there are any.
+    pub slug: f64,
+    /// Lines in the corners extending the edges of the trim.
+    pub crop_marks: bool,
+    /// Targets in the middle of each side, for lining up plates.
+    pub registration_marks: bool,
+    /// Patches of process colors and tints along the bottom, replacing the registration
+    /// mark there.
+    pub color_bars: bool,
+}
+
+impl PrintSetup {
+    /// Page boxes for `bleed`, without marks.
+    pub fn new(bleed: f64) -> Self {
+        Self {
+            bleed,
+            slug: 0.0,
+            crop_marks: false,
+            registration_marks: false,
+            color_bars: false,
+        }
+    }
+
+    /// All marks, in a slug half an inch wide.
+    pub fn with_marks(self) -> Self {
+        Self {
+            slug: self.slug.max(MIN_SLUG),
+            crop_marks: true,
+            registration_marks: true,
+            color_bars: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn has_marks(&self) -> bool {
+        self.crop_marks || self.registration_marks || self.color_bars
+    }
+
+    /// The width of the slug, widened to fit the marks if there are any.
+    fn slug(&self) -> f64 {
+        if self.has_marks() {
+            self.slug.max(MIN_SLUG)
+        } else {
+            self.slug
+        }
+    }
+
+    /// How far the media extends beyond the trim on each side.
+    pub(crate) fn margin(&self) -> f64 {
+        self.bleed + self.slug()
+    }
+
+    /// Writes the registration color space for the marks, a separation which prints on
+    /// every plate.
+    pub(crate) fn write_registration(chunk: &mut Chunk, id: Ref) {
+        let mut separation = chunk.color_space(id).separation(Name(b"All"));
+        separation.alternate_color_space().device_cmyk();
+        separation
+            .tint_exponential()
+            .domain([0.0, 1.0])
+            .c0([0.0; 4])
+            .c1([1.0; 4])
+            .n(1.0);
+    }
+
+    /// Draws the marks around a trim of `size` at the origin, in the registration color
+    /// space named [`REGISTRATION`] in the resources.
+    ///
+    /// Coordinates are those of the drawing, with y pointing down.
+    pub(crate) fn draw_marks(&self, content: &mut Content, size: Size, tolerance: f64) {
+        if !self.has_marks() {
+            return;
+        }
+        let (bleed, slug) = (self.bleed, self.slug());
+        let trim = size.to_rect();
+        content.save_state();
+        content.set_stroke_color_space(Name(REGISTRATION.as_bytes()));
+        content.set_stroke_color([1.0]);
+        content.set_line_width(0.25);
+        let line = |content: &mut Content, line: Line| {
+            push_path(content, &line, tolerance);
+            content.stroke();
+        };
+        if self.crop_marks {
+            // The marks stay out of the bleed, starting a little beyond it.
+            let (near, far) = (bleed + slug * 0.25, bleed + slug);
+            for x in [trim.x0, trim.x1] {
+                line(content, Line::new((x, trim.y0 - near), (x, trim.y0 - far)));
+                line(content, Line::new((x, trim.y1 + near), (x, trim.y1 + far)));
+            }
+            for y in [trim.y0, trim.y1] {
+                line(content, Line::new((trim.x0 - near, y), (trim.x0 - far, y)));
+                line(content, Line::new((trim.x1 + near, y), (trim.x1 + far, y)));
+            }
+        }
+        let middle = bleed + slug / 2.0;
+        let center = trim.center();
+        if self.registration_marks {
+            let radius = slug * 0.2;
+            let mut targets = vec![
+                (center.x, trim.y0 - middle),
+                (trim.x0 - middle, center.y),
+                (trim.x1 + middle, center.y),
+            ];
+            if !self.color_bars {
+                targets.push((center.x, trim.y1 + middle));
+            }
+            for (x, y) in targets {
+                push_path(content, &Circle::new((x, y), radius), tolerance);
+                content.stroke();
+                let reach = radius * 1.5;
+                line(content, Line::new((x - reach, y), (x + reach, y)));
+                line(content, Line::new((x, y - reach), (x, y + reach)));
+            }
+        }
+        if self.color_bars {
+            // Process colors, their overprints and tints of black.
+            let patches: [[f32; 4]; 11] = [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 1.0, 0.0],
+                [1.0, 0.0, 1.0, 0.0],
+                [1.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0, 0.75],
+                [0.0, 0.0, 0.0, 0.5],
+                [0.0, 0.0, 0.0, 0.25],
+                [0.0, 0.0, 0.0, 0.1],
+            ];
+            let side = slug * 0.4;
+            let x0 = center.x - side * patches.len() as f64 / 2.0;
+            for (i, [c, m, y, k]) in patches.into_iter().enumerate() {
+                let x = x0 + side * i as f64;
+                let y0 = trim.y1 + middle - side / 2.0;
+                content.set_fill_cmyk(c, m, y, k);
+                let patch = Rect::new(x, y0, x + side, y0 + side);
+                push_path(content, &patch, tolerance);
+                content.fill_nonzero();
+            }
+        }
+        content.restore_state();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_contains, fill, uncompressed, written};
+    use crate::{Metadata, Pdf, PdfOptions, SceneWhisperer};
+    use kurbo::Affine;
+    use peniko::Color;
+
+    #[test]
+    fn pdf_print_marks() {
+        let options = PdfOptions {
+            print: Some(PrintSetup::new(9.0).with_marks()),
+            ..uncompressed()
+        };
+        let mut pdf = Pdf::with_options(Size::new(100.0, 50.0), 0.1, options);
+        let metadata = Metadata::new().with_link("https://example.com");
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let ops = [fill(Color::BLACK)];
+        pdf.apply_paint_ops_with_metadata(&metadata, ops, Affine::IDENTITY, None, &square);
+        assert_contains(
+            &written(pdf),
+            &[
+                "/MediaBox [0 0 190 140]",
+                "/TrimBox [45 45 145 95]",
+                "/BleedBox [36 36 154 104]",
+                "/ArtBox [45 45 145 95]",
+                "1 0 0 -1 45 95 cm",
+                "/Rect [45 85 55 95]",
+                "/Separation /All /DeviceCMYK",
+                "/Registration CS\n1 SCN",
+                "0 0 0 0.5 k",
+            ],
+        );
+    }
+
+    #[test]
+    fn marks_widen_the_slug() {
+        let print = PrintSetup {
+            crop_marks: true,
+            ..PrintSetup::new(9.0)
+        };
+        assert_eq!(print.margin(), 45.0);
+        assert_eq!(PrintSetup::new(9.0).margin(), 9.0);
+        let mut content = Content::new();
+        print.draw_marks(&mut content, Size::new(100.0, 50.0), 0.1);
+        let content = String::from_utf8(content.finish()).unwrap();
+        // The marks go from a quarter of the way into the slug to its outer edge.
+        assert_contains(&content, &["0 -18 m\n0 -45 l"]);
+    }
+}
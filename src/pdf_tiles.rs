@@ -0,0 +1,427 @@
+use crate::metadata::Metadata;
+use crate::pdf_content::{compress, push_path, PdfContent};
+use crate::whisperer::*;
+use kurbo::{Affine, Line, Rect, Shape, Size};
+use pdf_writer::{Content, Name, Ref, Str, TextStr};
+use std::io;
+
+/// The paper and layout of the pages written by [`TiledPdf`], in points.
+#[derive(Clone, Debug)]
+pub struct TileOptions {
+    /// The size of each page, see [`TileOptions::A4`] and [`TileOptions::LETTER`].
+    pub paper: Size,
+    /// The width of the border around each page left blank, as printers can't print to
+    /// the edge of the paper.
+    pub margin: f64,
+    /// Points on paper per unit of the drawing.
+    pub scale: f64,
+    /// How far each page repeats the edge of its neighbors, so that they can be glued
+    /// together.
+    pub overlap: f64,
+    /// Outlines the printed area of each page and marks where its neighbors begin, with the
+    /// labels of the page and its neighbors in the margin.
+    pub assembly_marks: bool,
+    /// The Flate compression level of streams, as in [`PdfOptions`](crate::PdfOptions).
+    pub compression_level: u8,
+}
+
+impl TileOptions {
+    pub const A4: Size = Size::new(595.276, 841.89);
+    pub const LETTER: Size = Size::new(612.0, 792.0);
+
+    /// Pages of `paper` at full scale, with a half inch margin and overlap.
+    pub fn new(paper: Size) -> Self {
+        Self {
+            paper,
+            margin: 36.0,
+            scale: 1.0,
+            overlap: 36.0,
+            assembly_marks: false,
+            compression_level: 6,
+        }
+    }
+
+    /// The paper turned sideways.
+    pub fn landscape(self) -> Self {
+        Self {
+            paper: Size::new(self.paper.height, self.paper.width),
+            ..self
+        }
+    }
+
+    pub fn with_scale(self, scale: f64) -> Self {
+        Self { scale, ..self }
+    }
+
+    pub fn with_assembly_marks(self) -> Self {
+        Self {
+            assembly_marks: true,
+            ..self
+        }
+    }
+
+    /// The area of the page the drawing is printed in.
+    fn printable(&self) -> Size {
+        Size::new(
+            (self.paper.width - 2.0 * self.margin).max(0.0),
+            (self.paper.height - 2.0 * self.margin).max(0.0),
+        )
+    }
+
+    /// Why pages with these options can't be laid out, if they can't.
+    fn invalid(&self) -> Option<&'static str> {
+        let printable = self.printable();
+        if !(self.scale > 0.0 && self.scale.is_finite()) {
+            Some("the scale of tiles must be positive")
+        } else if printable.width <= 0.0 || printable.height <= 0.0 {
+            Some("the margin of tiles leaves nothing of the paper to print on")
+        } else if self.overlap >= printable.width || self.overlap >= printable.height {
+            Some("the overlap of tiles must be less than their printable area")
+        } else {
+            None
+        }
+    }
+
+    /// How far apart neighboring pages are on the drawing, in points.
+    fn step(&self) -> Size {
+        let printable = self.printable();
+        Size::new(
+            (printable.width - self.overlap).max(1.0),
+            (printable.height - self.overlap).max(1.0),
+        )
+    }
+}
+
+/// Writes a drawing too large for the paper to a PDF with a grid of pages, which are
+/// printed and put together to make up the drawing.
+///
+/// The pages go left to right, then top to bottom, and are labeled with the letter of
+/// their column and the number of their row, like `"B3"`. The drawing is written once and
+/// shown clipped on each page. Metadata is only used for print colors, viewer layers,
+/// links and bookmarks are left out.
+///
+/// A scene recorded in a [`DisplayList`](crate::DisplayList) is split into pages by
+/// replaying it into a `TiledPdf`.
+pub struct TiledPdf {
+    content: PdfContent,
+    size: Size,
+    options: TileOptions,
+}
+
+impl TiledPdf {
+    /// A document for a drawing `size` wide and high.
+    pub fn new(size: Size, tolerance: f64, options: TileOptions) -> Self {
+        Self {
+            content: PdfContent::new(tolerance),
+            size,
+            options,
+        }
+    }
+
+    pub fn options(&self) -> &TileOptions {
+        &self.options
+    }
+
+    /// The number of columns and rows of pages.
+    pub fn grid(&self) -> (usize, usize) {
+        let printable = self.options.printable();
+        let step = self.options.step();
+        let scaled = self.size * self.options.scale;
+        let count = |length: f64, printable: f64, step: f64| {
+            1 + ((length - printable) / step).ceil().max(0.0) as usize
+        };
+        (
+            count(scaled.width, printable.width, step.width),
+            count(scaled.height, printable.height, step.height),
+        )
+    }
+
+    /// The label of the page in `column` and `row`, counting from 0.
+    pub fn label(column: usize, row: usize) -> String {
+        let mut letters = Vec::new();
+        let mut x = column + 1;
+        while x > 0 {
+            letters.push(b'A' + ((x - 1) % 26) as u8);
+            x = (x - 1) / 26;
+        }
+        letters.reverse();
+        format!("{}{}", String::from_utf8(letters).unwrap(), row + 1)
+    }
+
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the scale isn't positive, the margin
+    /// leaves no printable area or the overlap is as large as it.
+    pub fn write(self, mut writer: impl io::Write) -> io::Result<()> {
+        if let Some(reason) = self.options.invalid() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, reason));
+        }
+        let (columns, rows) = self.grid();
+        let options = self.options;
+        let level = options.compression_level;
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let form_id = Ref::new(3);
+        let font_id = Ref::new(4);
+        let mut next_id = Ref::new(5);
+        let mut alloc = || next_id.bump();
+        let mut pdf = pdf_writer::Pdf::new();
+        // Nothing is put on viewer layers, so the form needs no optional content groups.
+        self.content
+            .write_form(&mut pdf, form_id, self.size, level, &mut alloc);
+        if options.assembly_marks {
+            pdf.type1_font(font_id)
+                .base_font(Name(b"Helvetica"))
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+        let pages: Vec<(Ref, Ref)> = (0..columns * rows).map(|_| (alloc(), alloc())).collect();
+        pdf.pages(page_tree_id)
+            .kids(pages.iter().map(|&(page_id, _)| page_id))
+            .count(pages.len() as i32);
+        let label_ids: Vec<Ref> = pages.iter().map(|_| alloc()).collect();
+
+        let paper = options.paper;
+        let printable = options.printable();
+        let step = options.step();
+        let scale = options.scale;
+        let area = Rect::from_origin_size((options.margin, options.margin), printable);
+        for (i, &(page_id, contents_id)) in pages.iter().enumerate() {
+            let (column, row) = (i % columns, i / columns);
+            let mut content = Content::new();
+            content.save_state();
+            push_path(&mut content, &area, 0.1);
+            content.clip_nonzero();
+            content.end_path();
+            // The form shows the drawing upright with its top left at (0, height), which
+            // goes to the top left of the area less the offset of the page.
+            let offset = (column as f64 * step.width, row as f64 * step.height);
+            content.transform([
+                scale as f32,
+                0.0,
+                0.0,
+                scale as f32,
+                (area.x0 - offset.0) as f32,
+                (area.y1 + offset.1 - scale * self.size.height) as f32,
+            ]);
+            content.x_object(Name(b"Fm0"));
+            content.restore_state();
+            if options.assembly_marks {
+                let neighbors = [
+                    (column + 1 < columns).then(|| Self::label(column + 1, row)),
+                    (row + 1 < rows).then(|| Self::label(column, row + 1)),
+                ];
+                draw_marks(
+                    &mut content,
+                    area,
+                    step,
+                    &Self::label(column, row),
+                    neighbors,
+                );
+            }
+            let (data, filter) = compress(&content.finish(), level);
+            let mut stream = pdf.stream(contents_id, &data);
+            if let Some(filter) = filter {
+                stream.filter(filter);
+            }
+            drop(stream);
+
+            let mut page = pdf.page(page_id);
+            page.parent(page_tree_id)
+                .media_box(pdf_writer::Rect::new(
+                    0.0,
+                    0.0,
+                    paper.width as f32,
+                    paper.height as f32,
+                ))
+                .contents(contents_id);
+            let mut page_resources = page.resources();
+            page_resources.x_objects().pair(Name(b"Fm0"), form_id);
+            if options.assembly_marks {
+                page_resources.fonts().pair(Name(b"F0"), font_id);
+            }
+            drop(page_resources);
+            drop(page);
+            pdf.indirect(label_ids[i])
+                .start::<pdf_writer::writers::PageLabel>()
+                .prefix(TextStr(&Self::label(column, row)));
+        }
+
+        let mut catalog = pdf.catalog(catalog_id);
+        catalog.pages(page_tree_id);
+        let mut labels = catalog.page_labels();
+        let mut nums = labels.nums();
+        for (i, &id) in label_ids.iter().enumerate() {
+            nums.insert(i as i32, id);
+        }
+        drop(nums);
+        drop(labels);
+        drop(catalog);
+        writer.write_all(&pdf.finish())
+    }
+}
+
+/// Outlines `area`, marks where the neighbors to the right and below begin `step` from its
+/// top left, and writes the labels of the page and those neighbors in the margin.
+fn draw_marks(
+    content: &mut Content,
+    area: Rect,
+    step: Size,
+    label: &str,
+    [right, below]: [Option<String>; 2],
+) {
+    const FONT_SIZE: f32 = 9.0;
+    content.save_state();
+    content.set_line_width(0.5);
+    content.set_stroke_gray(0.5);
+    push_path(content, &area, 0.1);
+    content.stroke();
+    content.set_dash_pattern([3.0, 3.0], 0.0);
+    let x = area.x0 + step.width;
+    if right.is_some() && x < area.x1 {
+        push_path(content, &Line::new((x, area.y0), (x, area.y1)), 0.1);
+        content.stroke();
+    }
+    let y = area.y1 - step.height;
+    if below.is_some() && y > area.y0 {
+        push_path(content, &Line::new((area.x0, y), (area.x1, y)), 0.1);
+        content.stroke();
+    }
+    content.set_fill_gray(0.0);
+    let text = |content: &mut Content, x: f64, y: f64, text: &str| {
+        content
+            .begin_text()
+            .set_font(Name(b"F0"), FONT_SIZE)
+            .next_line(x as f32, y as f32)
+            .show(Str(text.as_bytes()))
+            .end_text();
+    };
+    text(content, area.x0, area.y1 + 4.0, label);
+    if let Some(right) = right {
+        text(
+            content,
+            area.x1 + 4.0,
+            area.center().y,
+            &format!("{right} >"),
+        );
+    }
+    if let Some(below) = below {
+        let baseline = area.y0 - 4.0 - FONT_SIZE as f64;
+        text(content, area.center().x, baseline, &format!("{below} v"));
+    }
+    content.restore_state();
+}
+
+impl TiledPdf {
+    fn paint<'a, 'b>(
+        &mut self,
+        metadata: Option<&Metadata>,
+        ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        self.content.set_print_colors(metadata);
+        self.content.paint_ops(ops, transform, shape);
+        self.content.set_print_colors(None);
+    }
+}
+
+impl SceneWhisperer for TiledPdf {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.paint(None, [op], transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.paint(None, ops, transform, shape);
+    }
+
+    /// Print colors are used, the rest of the metadata is ignored.
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.paint(Some(metadata), ops, transform, shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_contains, fill};
+    use crate::DisplayList;
+    use peniko::Color;
+
+    #[test]
+    fn pdf_tiles() {
+        assert_eq!(TiledPdf::label(1, 2), "B3");
+        assert_eq!(TiledPdf::label(27, 0), "AB1");
+        let options = TileOptions {
+            compression_level: 0,
+            ..TileOptions::new(TileOptions::A4).with_assembly_marks()
+        };
+        let size = Size::new(1000.0, 600.0);
+        let mut list = DisplayList::new();
+        let plan = Rect::new(0.0, 0.0, 1000.0, 600.0);
+        list.apply_paint_op(fill(Color::BLACK), Affine::IDENTITY, None, &plan);
+        let mut pdf = TiledPdf::new(size, 0.1, options.clone());
+        list.replay(&mut pdf);
+        assert_eq!(pdf.grid(), (2, 1));
+        assert_eq!(
+            TiledPdf::new(size, 0.1, options.with_scale(2.0)).grid(),
+            (5, 2)
+        );
+        let mut out = Vec::new();
+        pdf.write(&mut out).unwrap();
+        assert_contains(
+            &String::from_utf8_lossy(&out),
+            &[
+                "/Count 2",
+                "/Subtype /Form",
+                "/Fm0 Do",
+                "/BaseFont /Helvetica",
+                "(A1) Tj",
+                "(B1 >) Tj",
+                "/P (B1)",
+                "/PageLabels",
+                "1 0 0 1 -451.276 205.89 cm",
+            ],
+        );
+    }
+
+    #[test]
+    fn pdf_tiles_invalid_options() {
+        let invalid = [
+            TileOptions {
+                margin: TileOptions::A4.width / 2.0,
+                ..TileOptions::new(TileOptions::A4)
+            },
+            TileOptions {
+                overlap: TileOptions::A4.width - 72.0,
+                ..TileOptions::new(TileOptions::A4)
+            },
+            TileOptions::new(TileOptions::A4).with_scale(0.0),
+        ];
+        for options in invalid {
+            let pdf = TiledPdf::new(Size::new(1000.0, 600.0), 0.1, options);
+            let err = pdf.write(io::sink()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
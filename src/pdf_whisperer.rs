@@ -1,179 +1,1206 @@
+use crate::cull::paint_bounds;
+use crate::icc::SRGB;
+use crate::metadata::{Metadata, ViewerLayer};
+use crate::pdf_content::{array_magic, compress, write_profile, PdfContent};
+use crate::pdf_marks::{PrintSetup, REGISTRATION};
 use crate::whisperer::*;
 use kurbo::{Affine, Shape};
-use std::io;
+use pdf_writer::{Chunk, Ref};
+use std::{fmt, io};
 
-/// Unsure about this, perhaps it would be better
-/// to implement ShapeWhisperer on `pdf_writer::Content`,
-/// having the caller add it to a `Pdf`.
+/// Options controlling the document written by [`Pdf`].
+#[derive(Clone, Debug)]
+pub struct PdfOptions {
+    /// Writes a tagged PDF with a structure tree, for accessibility.
+    ///
+    /// Ops with metadata which has a description or title become figures with it as their
+    /// alternate text, and so do the contents of layers pushed with such metadata.
+    /// Everything else is marked as an artifact, and links become link elements.
+    /// With a title, the document is identified as PDF/UA-1.
+    pub tagged: bool,
+    /// The natural language of the document, e.g. `"en-US"`.
+    pub language: Option<String>,
+    /// The title of the document, which viewers show instead of the file name.
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    /// When the document was created, there is no default so that output is reproducible.
+    pub creation_date: Option<PdfDate>,
+    /// Writes a PDF/A-2b document for archiving, with an sRGB output intent.
+    ///
+    /// Writing fails with a [`PdfAViolation`] if the drawing can't be represented faithfully.
+    pub pdf_a: bool,
+    /// The Flate compression level of streams, from 0 for none to 10 for the smallest
+    /// output, 6 by default.
+    pub compression_level: u8,
+    /// The layers listed in the layers panel of viewers, in order.
+    ///
+    /// Ops on these with [`Metadata::layer`] become optional content, ops on other layers
+    /// are always shown.
+    pub layers: Vec<ViewerLayer>,
+    /// Page boxes and marks for commercial printing, the page is the size of the drawing
+    /// without them.
+    pub print: Option<PrintSetup>,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            tagged: false,
+            language: None,
+            title: None,
+            author: None,
+            subject: None,
+            keywords: Vec::new(),
+            creation_date: None,
+            pdf_a: false,
+            compression_level: 6,
+            layers: Vec::new(),
+            print: None,
+        }
+    }
+}
+
+/// A date and time in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PdfDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl PdfDate {
+    /// Midnight on the given day.
+    pub fn new(year: u16, month: u8, day: u8) -> Self {
+        Self {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+
+    pub fn with_time(self, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            hour,
+            minute,
+            second,
+            ..self
+        }
+    }
+
+    fn to_pdf(self) -> pdf_writer::Date {
+        pdf_writer::Date::new(self.year)
+            .month(self.month)
+            .day(self.day)
+            .hour(self.hour)
+            .minute(self.minute)
+            .second(self.second)
+            .utc_offset_hour(0)
+    }
+}
+
+impl fmt::Display for PdfDate {
+    /// Formats the date as in XMP, e.g. `2024-03-01T12:00:00Z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Why a drawing can't be written as PDF/A.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PdfAViolation {
+    /// The page is smaller than 3 or larger than 14400 units in some direction.
+    PageSize,
+    /// Gradient brushes aren't supported and would be left out.
+    Gradient,
+    /// Image brushes aren't supported and would be left out.
+    Image,
+    /// DeviceCMYK colors, and spot colors with a CMYK alternate, don't match the sRGB
+    /// output intent.
+    Cmyk,
+    /// Printer's marks are in the registration color, which doesn't match the sRGB output
+    /// intent.
+    PrintMarks,
+}
+
+impl fmt::Display for PdfAViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PdfAViolation::PageSize => {
+                "PDF/A pages must be between 3 and 14400 units wide and high"
+            }
+            PdfAViolation::Gradient => "gradient brushes can't be written to PDF/A",
+            PdfAViolation::Image => "image brushes can't be written to PDF/A",
+            PdfAViolation::Cmyk => "CMYK colors can't be written to PDF/A",
+            PdfAViolation::PrintMarks => "printer's marks can't be written to PDF/A",
+        })
+    }
+}
+
+impl std::error::Error for PdfAViolation {}
+
+/// An element of the structure tree of a tagged PDF.
+enum StructNode {
+    Figure {
+        alt: String,
+        // Marked content sequences, with the transparency group each is in, if any.
+        mcids: Vec<(i32, Option<usize>)>,
+        // The area painted in page coordinates.
+        bbox: Option<kurbo::Rect>,
+    },
+    /// The index of a link annotation.
+    Link(usize),
+}
+
+/// A link annotation, in page coordinates.
+struct Link {
+    rect: kurbo::Rect,
+    /// The corners of the area when it is rotated, counterclockwise.
+    quad: Option<[kurbo::Point; 4]>,
+    url: String,
+    title: Option<String>,
+}
+
+/// An entry in the document outline.
+struct Bookmark {
+    title: String,
+    /// Where the entry goes to, the top left of the area in page coordinates.
+    at: kurbo::Point,
+    parent: Option<usize>,
+}
+
+/// A single page document around a [`PdfContent`], which can be used directly to paint
+/// into other documents.
 pub struct Pdf {
     pdf: pdf_writer::Pdf,
-    content: pdf_writer::Content,
-    tolerance: f64,
+    content: PdfContent,
     size: kurbo::Size,
+    links: Vec<Link>,
+    // The named destinations, at the top left of areas in page coordinates.
+    destinations: Vec<(String, kurbo::Point)>,
+    // The outline entries in the order they were added, parents first.
+    bookmarks: Vec<Bookmark>,
+    // The bookmark each open layer is nested under, if any.
+    layer_bookmarks: Vec<Option<usize>>,
+    options: PdfOptions,
+    // Children of the document structure element in order, only used when tagged.
+    structure: Vec<StructNode>,
+    // The figure in `structure` each open layer belongs to, if any.
+    layer_figures: Vec<Option<usize>>,
+    next_mcid: i32,
 }
 ///
 /// Writer for single page pdfs of unrasterized vector images
 impl Pdf {
     pub fn new(size: kurbo::Size, tolerance: f64) -> Self {
+        Self::with_options(size, tolerance, PdfOptions::default())
+    }
+
+    pub fn with_options(size: kurbo::Size, tolerance: f64, options: PdfOptions) -> Self {
         use pdf_writer::{Pdf, Ref};
 
         // Define some indirect reference ids we'll use.
-        let catalog_id = Ref::new(1);
         let page_tree_id = Ref::new(2);
         let page_id = Ref::new(3);
 
-        // Write a document catalog and a page tree with one A4 page that uses no resources.
+        // Write a page tree with one page, the catalog is written last.
         let mut pdf = Pdf::new();
-        pdf.catalog(catalog_id).pages(page_tree_id);
         pdf.pages(page_tree_id).kids([page_id]).count(1);
 
-        let mut content = pdf_writer::Content::new();
-        content.transform(array_magic(
-            (Affine::translate((0.0, size.height)) * Affine::FLIP_Y).as_coeffs(),
-            |x| x as f32,
-        ));
-
-        Self {
+        let mut pdf = Self {
             pdf,
-            content,
-            tolerance,
+            content: PdfContent::new(tolerance),
             size,
-        }
+            links: Vec::new(),
+            destinations: Vec::new(),
+            bookmarks: Vec::new(),
+            layer_bookmarks: Vec::new(),
+            options,
+            structure: Vec::new(),
+            layer_figures: Vec::new(),
+            next_mcid: 0,
+        };
+        let page = pdf.page_transform();
+        pdf.content
+            .content_mut()
+            .transform(array_magic(page.as_coeffs(), |x| x as f32));
+        pdf
+    }
+
+    /// Maps the drawing to page coordinates, with y pointing up and room for the bleed
+    /// and marks around it.
+    fn page_transform(&self) -> Affine {
+        let margin = self.options.print.map_or(0.0, |print| print.margin());
+        Affine::translate((margin, margin + self.size.height)) * Affine::FLIP_Y
+    }
+
+    /// The trim and media box, in page coordinates.
+    fn page_boxes(&self) -> (kurbo::Rect, kurbo::Rect) {
+        let trim = self
+            .page_transform()
+            .transform_rect_bbox(self.size.to_rect());
+        let margin = self.options.print.map_or(0.0, |print| print.margin());
+        (trim, trim.inflate(margin, margin))
+    }
+
+    pub fn options(&self) -> &PdfOptions {
+        &self.options
     }
 
+    /// Writes the document, failing with a [`PdfAViolation`] if it should be PDF/A and
+    /// can't be.
     pub fn write(mut self, mut writer: impl io::Write) -> io::Result<()> {
-        use pdf_writer::Ref;
+        use pdf_writer::types::{
+            ActionType, AnnotationFlags, AnnotationType, OutputIntentSubtype, PageMode, StructRole,
+            TabOrder,
+        };
+        use pdf_writer::writers::{Annotation, Destination, OutputIntent, StructTreeRoot};
+        use pdf_writer::{Name, Str, TextStr};
+        let pdf_a = self.options.pdf_a;
+        let (trim, media) = self.page_boxes();
+        if pdf_a {
+            let valid = 3.0..=14400.0;
+            let print = self.options.print;
+            let violation = &mut self.content.violation;
+            if !valid.contains(&media.width()) || !valid.contains(&media.height()) {
+                violation.get_or_insert(PdfAViolation::PageSize);
+            }
+            if print.is_some_and(|print| print.has_marks()) {
+                violation.get_or_insert(PdfAViolation::PrintMarks);
+            }
+            if let Some(violation) = *violation {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, violation));
+            }
+        }
+        let catalog_id = Ref::new(1);
         let page_tree_id = Ref::new(2);
         let page_id = Ref::new(3);
         let contents_id = Ref::new(4);
-        self.pdf.stream(contents_id, &self.content.finish());
-        self.pdf
-            .page(page_id)
-            .parent(page_tree_id)
-            .media_box(pdf_writer::Rect::new(
-                0.0,
-                0.0,
-                self.size.width as f32,
-                self.size.height as f32,
-            ))
-            .contents(contents_id)
-            .resources();
+        let tagged = self.options.tagged;
+        let mut next_id = Ref::new(5);
+        let mut alloc = || next_id.bump();
+        let level = self.options.compression_level;
+        // Key 0 of the parent tree is the page, followed by the links and the groups.
+        let group_key = 1 + self.links.len() as i32;
+        if tagged {
+            self.content.group_struct_parents = Some(group_key);
+        }
+        let mut resources = self
+            .content
+            .write_resources(&mut self.pdf, level, &mut alloc);
+        let marks = self.options.print.filter(PrintSetup::has_marks);
+        if marks.is_some() {
+            let id = alloc();
+            PrintSetup::write_registration(&mut self.pdf, id);
+            resources.push_color_space(REGISTRATION, id);
+        }
+        let annotation_ids: Vec<Ref> = self.links.iter().map(|_| alloc()).collect();
+
+        let tolerance = self.content.tolerance();
+        let mut content = self.content.finish();
+        if let Some(print) = marks {
+            if tagged {
+                content.begin_marked_content(Name(b"Artifact"));
+            }
+            print.draw_marks(&mut content, self.size, tolerance);
+            if tagged {
+                content.end_marked_content();
+            }
+        }
+        let content = content.finish();
+        self.pdf.set_file_id(file_id(&content, &self.options));
+        let (content, filter) = compress(&content, level);
+        let mut stream = self.pdf.stream(contents_id, &content);
+        if let Some(filter) = filter {
+            stream.filter(filter);
+        }
+        drop(stream);
+        let mut page = self.pdf.page(page_id);
+        page.parent(page_tree_id)
+            .media_box(pdf_rect(media))
+            .contents(contents_id);
+        if let Some(print) = self.options.print {
+            let bleed = trim.inflate(print.bleed, print.bleed);
+            page.trim_box(pdf_rect(trim))
+                .bleed_box(pdf_rect(bleed))
+                .art_box(pdf_rect(trim));
+        }
+        if !annotation_ids.is_empty() {
+            page.insert(Name(b"Annots"))
+                .array()
+                .items(annotation_ids.iter().copied());
+        }
+        if tagged {
+            page.struct_parents(0);
+            if !annotation_ids.is_empty() {
+                page.tab_order(TabOrder::StructureOrder);
+            }
+        }
+        resources.write(&mut page.resources());
+        drop(page);
+        for (i, link) in self.links.iter().enumerate() {
+            let mut annotation = self.pdf.indirect(annotation_ids[i]).start::<Annotation>();
+            annotation
+                .subtype(AnnotationType::Link)
+                .rect(pdf_rect(link.rect))
+                .border(0.0, 0.0, 0.0, None)
+                .contents(TextStr(link.title.as_deref().unwrap_or(&link.url)));
+            if let Some(quad) = link.quad {
+                annotation.quad_points(quad.into_iter().flat_map(|p| [p.x as f32, p.y as f32]));
+            }
+            if pdf_a {
+                annotation.flags(AnnotationFlags::PRINT);
+            }
+            if tagged {
+                // Key 0 of the parent tree is the page.
+                annotation.struct_parent(1 + i as i32);
+            }
+            let mut action = annotation.action();
+            match link.url.strip_prefix('#') {
+                Some(name) => {
+                    action
+                        .action_type(ActionType::GoTo)
+                        .pair(Name(b"D"), Str(name.as_bytes()));
+                }
+                None => {
+                    action
+                        .action_type(ActionType::Uri)
+                        .uri(Str(link.url.as_bytes()));
+                }
+            }
+        }
+        // Named destinations sorted for the name tree, the first of duplicates is kept.
+        let mut destinations: Vec<_> = self.destinations.iter().collect();
+        destinations.sort_by(|(a, _), (b, _)| a.cmp(b));
+        destinations.dedup_by(|(a, _), (b, _)| a == b);
+        let destination_ids: Vec<Ref> = destinations.iter().map(|_| alloc()).collect();
+        for (&(_, at), &id) in destinations.iter().zip(&destination_ids) {
+            self.pdf
+                .indirect(id)
+                .start::<Destination>()
+                .page(page_id)
+                .xyz(at.x as f32, at.y as f32, None);
+        }
+        let outline_id = (!self.bookmarks.is_empty()).then(&mut alloc);
+        let bookmark_ids: Vec<Ref> = self.bookmarks.iter().map(|_| alloc()).collect();
+        if let Some(outline_id) = outline_id {
+            write_outline(
+                &mut self.pdf,
+                &self.bookmarks,
+                outline_id,
+                &bookmark_ids,
+                page_id,
+            );
+        }
+
+        let options = &self.options;
+        let has_info = options.title.is_some()
+            || options.author.is_some()
+            || options.subject.is_some()
+            || !options.keywords.is_empty()
+            || options.creation_date.is_some();
+        let xmp_id = (has_info || pdf_a).then(&mut alloc);
+        // The output intent shares the profile the content is tagged with, if any.
+        let shared_icc = resources.srgb_profile;
+        let icc_id = pdf_a.then(|| shared_icc.unwrap_or_else(&mut alloc));
+        // The optional content groups in the order of the layers panel.
+        let mut groups: Vec<(usize, Ref)> = resources
+            .optional_content_groups()
+            .filter_map(|(name, id)| {
+                let i = options.layers.iter().position(|layer| layer.name == name)?;
+                Some((i, id))
+            })
+            .collect();
+        groups.sort();
+        let mut catalog = self.pdf.catalog(catalog_id);
+        catalog.pages(page_tree_id);
+        if !destinations.is_empty() {
+            let mut names = catalog.names();
+            let mut tree = names.destinations();
+            let mut entries = tree.names();
+            for (&(name, _), &id) in destinations.iter().zip(&destination_ids) {
+                entries.insert(Str(name.as_bytes()), id);
+            }
+        }
+        if let Some(outline_id) = outline_id {
+            catalog
+                .outlines(outline_id)
+                .page_mode(PageMode::UseOutlines);
+        }
+        if !groups.is_empty() {
+            let mut properties = catalog.insert(Name(b"OCProperties")).dict();
+            properties
+                .insert(Name(b"OCGs"))
+                .array()
+                .items(groups.iter().map(|&(_, id)| id));
+            let mut config = properties.insert(Name(b"D")).dict();
+            config.pair(Name(b"Name"), TextStr("Layers"));
+            config
+                .insert(Name(b"Order"))
+                .array()
+                .items(groups.iter().map(|&(_, id)| id));
+            config.insert(Name(b"OFF")).array().items(
+                groups
+                    .iter()
+                    .filter(|&&(i, _)| !options.layers[i].visible)
+                    .map(|&(_, id)| id),
+            );
+        }
+        if let Some(language) = &self.options.language {
+            catalog.lang(TextStr(language));
+        }
+        if self.options.title.is_some() {
+            catalog
+                .viewer_preferences()
+                .pair(Name(b"DisplayDocTitle"), true);
+        }
+        let tree_id = tagged.then(&mut alloc);
+        if let Some(tree_id) = tree_id {
+            catalog.mark_info().marked(true);
+            catalog.pair(Name(b"StructTreeRoot"), tree_id);
+        }
+        if let Some(xmp_id) = xmp_id {
+            catalog.metadata(xmp_id);
+        }
+        if let Some(icc_id) = icc_id {
+            catalog
+                .insert(Name(b"OutputIntents"))
+                .array()
+                .push()
+                .start::<OutputIntent>()
+                .subtype(OutputIntentSubtype::PDFA)
+                .output_condition_identifier(TextStr(SRGB.description))
+                .info(TextStr(SRGB.description))
+                .dest_output_profile(icc_id);
+        }
+        drop(catalog);
+        if has_info || pdf_a {
+            let info_id = alloc();
+            let mut info = self.pdf.document_info(info_id);
+            if let Some(title) = &options.title {
+                info.title(TextStr(title));
+            }
+            if let Some(author) = &options.author {
+                info.author(TextStr(author));
+            }
+            if let Some(subject) = &options.subject {
+                info.subject(TextStr(subject));
+            }
+            if !options.keywords.is_empty() {
+                info.keywords(TextStr(&options.keywords.join(", ")));
+            }
+            if let Some(date) = options.creation_date {
+                info.creation_date(date.to_pdf());
+            }
+            info.producer(TextStr(PRODUCER));
+        }
+        if let Some(xmp_id) = xmp_id {
+            let xmp = xmp(options);
+            self.pdf.metadata(xmp_id, xmp.as_bytes());
+        }
+        if let Some(icc_id) = icc_id.filter(|_| shared_icc.is_none()) {
+            write_profile(&mut self.pdf, icc_id, &SRGB, level);
+        }
+
+        if let Some(tree_id) = tree_id {
+            let document_id = alloc();
+            let page_mcids_id = alloc();
+            let group_ids: Vec<Ref> = resources.groups().collect();
+            let group_mcids_ids: Vec<Ref> = group_ids.iter().map(|_| alloc()).collect();
+            let node_ids: Vec<Ref> = self.structure.iter().map(|_| alloc()).collect();
+            let mut tree = self.pdf.indirect(tree_id).start::<StructTreeRoot>();
+            tree.child(document_id);
+            let mut parent_tree = tree.parent_tree();
+            let mut nums = parent_tree.nums();
+            nums.insert(0, page_mcids_id);
+            for (node, id) in self.structure.iter().zip(&node_ids) {
+                if let StructNode::Link(i) = node {
+                    nums.insert(1 + *i as i32, *id);
+                }
+            }
+            for (i, &id) in group_mcids_ids.iter().enumerate() {
+                nums.insert(group_key + i as i32, id);
+            }
+            drop(nums);
+            drop(parent_tree);
+            tree.parent_tree_next_key(group_key + group_ids.len() as i32);
+            drop(tree);
+
+            let mut document = self.pdf.struct_element(document_id);
+            document.kind(StructRole::Document).parent(tree_id);
+            let mut children = document.children();
+            for &id in &node_ids {
+                children.struct_element(id);
+            }
+            drop(children);
+            drop(document);
+
+            // The structure element each marked content sequence belongs to, by MCID, on
+            // the page and in each group.
+            let mut page_mcids = vec![document_id; self.next_mcid as usize];
+            let mut group_mcids = vec![page_mcids.clone(); group_ids.len()];
+            for (node, &id) in self.structure.iter().zip(&node_ids) {
+                let mut element = self.pdf.struct_element(id);
+                element.parent(document_id).page(page_id);
+                match node {
+                    StructNode::Figure { alt, mcids, bbox } => {
+                        element.kind(StructRole::Figure).alt(TextStr(alt));
+                        if let Some(bbox) = bbox {
+                            element.attributes().push().layout().bbox(pdf_rect(*bbox));
+                        }
+                        let mut children = element.children();
+                        for &(mcid, group) in mcids {
+                            match group {
+                                Some(group) => {
+                                    children
+                                        .marked_content_ref()
+                                        .marked_content_id(mcid)
+                                        .page(page_id)
+                                        .stream(group_ids[group]);
+                                }
+                                None => {
+                                    children.marked_content_id(mcid);
+                                }
+                            }
+                        }
+                        drop(children);
+                        for &(mcid, group) in mcids {
+                            match group {
+                                Some(group) => group_mcids[group][mcid as usize] = id,
+                                None => page_mcids[mcid as usize] = id,
+                            }
+                        }
+                    }
+                    StructNode::Link(i) => {
+                        let link = &self.links[*i];
+                        element
+                            .kind(StructRole::Link)
+                            .alt(TextStr(link.title.as_deref().unwrap_or(&link.url)));
+                        element
+                            .object_child()
+                            .page(page_id)
+                            .object(annotation_ids[*i]);
+                    }
+                }
+            }
+            self.pdf.indirect(page_mcids_id).array().items(page_mcids);
+            for (&id, mcids) in group_mcids_ids.iter().zip(group_mcids) {
+                self.pdf.indirect(id).array().items(mcids);
+            }
+        }
         let _ = writer.write(&self.pdf.finish())?;
         Ok(())
     }
 }
 
-fn array_magic<T, U, const SZ: usize, F: Fn(T) -> U>(src: [T; SZ], f: F) -> [U; SZ]
-where
-    T: Copy,
-{
-    use std::mem::MaybeUninit;
+/// The producer recorded in the document metadata.
+const PRODUCER: &str = "selvage";
 
-    let dest: [U; SZ] = unsafe {
-        let mut dest = MaybeUninit::uninit();
-        // safety, for i in 0..SZ dest[i] = f(x) initializing the entire range.
-        for (i, &x) in src.iter().enumerate() {
-            (dest.as_mut_ptr() as *mut U).add(i).write(f(x));
-        }
-        dest.assume_init()
+/// A file identifier derived from the content and metadata, so that it is reproducible.
+fn file_id(content: &[u8], options: &PdfOptions) -> (Vec<u8>, Vec<u8>) {
+    use std::hash::{Hash, Hasher};
+    let mut id = Vec::with_capacity(16);
+    for seed in [0u8, 1] {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        seed.hash(&mut hasher);
+        content.hash(&mut hasher);
+        options.title.hash(&mut hasher);
+        options.author.hash(&mut hasher);
+        options.subject.hash(&mut hasher);
+        options.keywords.hash(&mut hasher);
+        options.creation_date.hash(&mut hasher);
+        id.extend(hasher.finish().to_be_bytes());
+    }
+    (id.clone(), id)
+}
+
+/// Escapes text for XML element content.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// An XMP packet matching the document information dictionary, identifying the document
+/// as PDF/A-2b and PDF/UA-1 when it should be.
+fn xmp(options: &PdfOptions) -> String {
+    let mut properties = String::new();
+    let mut property = |name: &str, value: String| {
+        properties += &format!("<{name}>{value}</{name}>\n");
+    };
+    let alt = |text: &str| {
+        format!(
+            "<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>",
+            escape_xml(text)
+        )
     };
+    property("dc:format", "application/pdf".into());
+    if let Some(title) = &options.title {
+        property("dc:title", alt(title));
+    }
+    if let Some(author) = &options.author {
+        property(
+            "dc:creator",
+            format!("<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>", escape_xml(author)),
+        );
+    }
+    if let Some(subject) = &options.subject {
+        property("dc:description", alt(subject));
+    }
+    if !options.keywords.is_empty() {
+        property("pdf:Keywords", escape_xml(&options.keywords.join(", ")));
+    }
+    property("pdf:Producer", PRODUCER.into());
+    if let Some(date) = options.creation_date {
+        property("xmp:CreateDate", date.to_string());
+    }
+    if options.pdf_a {
+        property("pdfaid:part", "2".into());
+        property("pdfaid:conformance", "B".into());
+    }
+    // PDF/UA requires a title, so untitled documents are only tagged.
+    if options.tagged && options.title.is_some() {
+        property("pdfuaid:part", "1".into());
+    }
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+<rdf:Description rdf:about=\"\" \
+xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" \
+xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\" \
+xmlns:pdfuaid=\"http://www.aiim.org/pdfua/ns/id/\">
+{properties}</rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>
+<?xpacket end=\"w\"?>"
+    )
+}
 
-    dest
+fn pdf_rect(rect: kurbo::Rect) -> pdf_writer::Rect {
+    pdf_writer::Rect::new(
+        rect.x0 as f32,
+        rect.y0 as f32,
+        rect.x1 as f32,
+        rect.y1 as f32,
+    )
 }
 
-impl SceneWhisperer for Pdf {
-    fn apply_paint_op(
+impl Pdf {
+    /// Adds a link annotation covering what `ops` paint, if `metadata` has a link.
+    ///
+    /// The annotation gets the corners of the area as quad points when the ops are rotated
+    /// or skewed, so that the link doesn't cover the bounding box around them.
+    fn add_link(
         &mut self,
-        op: PaintOpRef<'_, '_>,
+        metadata: &Metadata,
+        ops: &[PaintOpRef<'_, '_>],
         transform: Affine,
-        _brush_transform: Option<Affine>,
         shape: &impl Shape,
     ) {
-        self.content.save_state();
-        self.content
-            .transform(array_magic(transform.as_coeffs(), |x| x as f32));
-        if let Some(line) = shape.as_line() {
-            self.content.move_to(line.p0.x as f32, line.p0.y as f32);
-            self.content.line_to(line.p1.x as f32, line.p1.y as f32);
-        } else if let Some(r) = shape.as_rect() {
-            self.content
-                .rect(r.x0 as f32, r.x0 as f32, r.x1 as f32, r.x1 as f32);
+        let Some(url) = &metadata.link else {
+            return;
+        };
+        let [_, b, c, ..] = transform.as_coeffs();
+        let (rect, quad) = if b == 0.0 && c == 0.0 {
+            let Some(bounds) = self.painted_bounds(ops, transform, shape) else {
+                return;
+            };
+            (bounds, None)
         } else {
-            let path = shape.into_path(self.tolerance);
-            for elem in path {
-                match elem {
-                    kurbo::PathEl::MoveTo(pt) => {
-                        self.content.move_to(pt.x as f32, pt.y as f32);
-                    }
-                    kurbo::PathEl::LineTo(pt) => {
-                        self.content.line_to(pt.x as f32, pt.y as f32);
-                    }
-                    kurbo::PathEl::CurveTo(a, b, c) => {
-                        self.content.cubic_to(
-                            a.x as f32, a.y as f32, b.x as f32, b.y as f32, c.x as f32, c.y as f32,
-                        );
-                    }
-                    kurbo::PathEl::ClosePath => {
-                        self.content.close_path();
-                    }
-                    kurbo::PathEl::QuadTo(_a, _b) => {
-                        unimplemented!()
-                    }
+            let Some(local) = painted_bounds(ops, Affine::IDENTITY, shape) else {
+                return;
+            };
+            let to_page = self.page_transform() * transform;
+            let mut quad = [
+                (local.x0, local.y0),
+                (local.x1, local.y0),
+                (local.x1, local.y1),
+                (local.x0, local.y1),
+            ]
+            .map(|corner| to_page * kurbo::Point::from(corner));
+            if to_page.determinant() < 0.0 {
+                quad.reverse();
+            }
+            let rect = to_page.transform_rect_bbox(local);
+            (rect, Some(quad))
+        };
+        self.links.push(Link {
+            rect,
+            quad,
+            url: url.clone(),
+            title: metadata.title.clone(),
+        });
+        if self.options.tagged {
+            self.structure.push(StructNode::Link(self.links.len() - 1));
+        }
+    }
+
+    /// The bounds of what `ops` paint in page coordinates.
+    fn painted_bounds(
+        &self,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        shape: &impl Shape,
+    ) -> Option<kurbo::Rect> {
+        let bounds = painted_bounds(ops, transform, shape)?;
+        Some(self.page_transform().transform_rect_bbox(bounds))
+    }
+
+    /// Adds a named destination for the `id` of `metadata`, and an outline entry for its
+    /// bookmark nested under the layers open and pushing its own for the layers `ops` push.
+    fn add_destinations(
+        &mut self,
+        metadata: Option<&Metadata>,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        let id = metadata.and_then(|metadata| metadata.id.as_ref());
+        let title = metadata.and_then(|metadata| metadata.bookmark.as_ref());
+        let at = (id.is_some() || title.is_some())
+            .then(|| self.painted_bounds(ops, transform, shape))
+            .flatten()
+            .map(|bounds| kurbo::Point::new(bounds.x0, bounds.y1));
+        if let Some(id) = id {
+            if let Some(at) = at {
+                self.destinations.push((id.clone(), at));
+            }
+        }
+        let parent = self.layer_bookmarks.last().copied().flatten();
+        let mut bookmark = parent;
+        if let Some(title) = title {
+            if let Some(at) = at {
+                self.bookmarks.push(Bookmark {
+                    title: title.clone(),
+                    at,
+                    parent,
+                });
+                bookmark = Some(self.bookmarks.len() - 1);
+            }
+        }
+        for op in ops {
+            match op {
+                PaintOpRef::PushLayer { .. } => self.layer_bookmarks.push(bookmark),
+                PaintOpRef::PopLayer => {
+                    self.layer_bookmarks.pop();
                 }
+                PaintOpRef::Fill { .. } | PaintOpRef::Stroke { .. } => (),
             }
         }
-        match op {
-            PaintOpRef::Fill { style, brush } => {
-                match brush {
-                    peniko::BrushRef::Solid(x) => {
-                        self.content
-                            .set_fill_rgb(x.r as f32, x.g as f32, x.b as f32);
-                    }
-                    peniko::BrushRef::Gradient(_x) => {}
-                    peniko::BrushRef::Image(_x) => {}
+    }
+}
+
+/// Writes the outline `id` to `chunk`, with the item `ids[i]` for each bookmark and all of
+/// them open.
+fn write_outline(chunk: &mut Chunk, bookmarks: &[Bookmark], id: Ref, ids: &[Ref], page: Ref) {
+    use pdf_writer::TextStr;
+    // The children of the outline and of each bookmark, in order.
+    let mut top = Vec::new();
+    let mut kids = vec![Vec::new(); bookmarks.len()];
+    for (i, bookmark) in bookmarks.iter().enumerate() {
+        match bookmark.parent {
+            Some(parent) => kids[parent].push(i),
+            None => top.push(i),
+        }
+    }
+    // The number of descendants of each item, children come after their parents.
+    let mut counts = vec![0; bookmarks.len()];
+    for (i, bookmark) in bookmarks.iter().enumerate().rev() {
+        if let Some(parent) = bookmark.parent {
+            counts[parent] += counts[i] + 1;
+        }
+    }
+    let mut outline = chunk.outline(id);
+    outline
+        .first(ids[top[0]])
+        .last(ids[top[top.len() - 1]])
+        .count(bookmarks.len() as i32);
+    drop(outline);
+    for (i, bookmark) in bookmarks.iter().enumerate() {
+        let mut item = chunk.outline_item(ids[i]);
+        item.title(TextStr(&bookmark.title))
+            .parent(bookmark.parent.map_or(id, |parent| ids[parent]));
+        let siblings = bookmark.parent.map_or(&top, |parent| &kids[parent]);
+        let index = siblings.iter().position(|&x| x == i).unwrap();
+        if index > 0 {
+            item.prev(ids[siblings[index - 1]]);
+        }
+        if let Some(&next) = siblings.get(index + 1) {
+            item.next(ids[next]);
+        }
+        if let (Some(&first), Some(&last)) = (kids[i].first(), kids[i].last()) {
+            item.first(ids[first]).last(ids[last]).count(counts[i]);
+        }
+        item.dest()
+            .page(page)
+            .xyz(bookmark.at.x as f32, bookmark.at.y as f32, None);
+    }
+}
+
+/// The bounds of what `ops` paint, ignoring layers being popped.
+fn painted_bounds(
+    ops: &[PaintOpRef<'_, '_>],
+    transform: Affine,
+    shape: &impl Shape,
+) -> Option<kurbo::Rect> {
+    ops.iter()
+        .filter(|op| !matches!(op, PaintOpRef::PopLayer))
+        .map(|op| paint_bounds(op, transform, shape))
+        .reduce(|a, b| a.union(b))
+}
+
+impl Pdf {
+    fn add_figure(&mut self, alt: &str) -> usize {
+        self.structure.push(StructNode::Figure {
+            alt: alt.to_owned(),
+            mcids: Vec::new(),
+            bbox: None,
+        });
+        self.structure.len() - 1
+    }
+
+    /// The index of the viewer layer `name` in the options.
+    fn viewer_layer(&self, name: &str) -> Option<usize> {
+        self.options
+            .layers
+            .iter()
+            .position(|layer| layer.name == name)
+    }
+
+    /// Paints `ops`, tagging them when the document is tagged.
+    fn paint(
+        &mut self,
+        metadata: Option<&Metadata>,
+        ops: Vec<PaintOpRef<'_, '_>>,
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        let layer = metadata
+            .and_then(|metadata| metadata.layer.as_deref())
+            .filter(|name| self.viewer_layer(name).is_some());
+        self.content.set_viewer_layer(layer);
+        self.content.set_print_colors(metadata);
+        if self.options.tagged {
+            self.paint_tagged(metadata, &ops, transform, shape);
+        } else {
+            self.content
+                .paint_ops(ops.iter().copied(), transform, shape);
+        }
+        self.content.set_viewer_layer(None);
+        self.content.set_print_colors(None);
+        // After painting, so the link follows the figure in the structure tree.
+        if let Some(metadata) = metadata {
+            self.add_link(metadata, &ops, transform, shape);
+        }
+        self.add_destinations(metadata, &ops, transform, shape);
+    }
+
+    fn paint_tagged(
+        &mut self,
+        metadata: Option<&Metadata>,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        let alt = metadata.and_then(|metadata| {
+            metadata
+                .description
+                .as_deref()
+                .or(metadata.title.as_deref())
+        });
+        // The figure for the ops of this call, created when first needed.
+        let mut figure = None;
+        let mut target = |this: &mut Self| match alt {
+            Some(alt) => Some(*figure.get_or_insert_with(|| this.add_figure(alt))),
+            None => this.layer_figures.last().copied().flatten(),
+        };
+        let mut rest = ops;
+        while let Some(op) = rest.first() {
+            match *op {
+                PaintOpRef::PushLayer { blend, alpha } => {
+                    let target = target(self);
+                    self.content.push_layer(blend, alpha, transform, shape);
+                    self.layer_figures.push(target);
+                    rest = &rest[1..];
                 }
-                match style {
-                    peniko::Fill::EvenOdd => {
-                        self.content.fill_even_odd();
-                    }
-                    peniko::Fill::NonZero => {
-                        self.content.fill_nonzero();
-                    }
+                PaintOpRef::PopLayer => {
+                    self.content.pop_layer();
+                    self.layer_figures.pop();
+                    rest = &rest[1..];
                 }
-            }
-            PaintOpRef::Stroke { style, brush } => {
-                match brush {
-                    peniko::BrushRef::Solid(x) => {
-                        self.content
-                            .set_fill_rgb(x.r as f32, x.g as f32, x.b as f32);
-                    }
-                    peniko::BrushRef::Gradient(_x) => {}
-                    peniko::BrushRef::Image(_x) => {}
+                PaintOpRef::Fill { .. } | PaintOpRef::Stroke { .. } => {
+                    let len = rest
+                        .iter()
+                        .position(|op| {
+                            matches!(op, PaintOpRef::PushLayer { .. } | PaintOpRef::PopLayer)
+                        })
+                        .unwrap_or(rest.len());
+                    let (run, tail) = rest.split_at(len);
+                    let target = target(self);
+                    self.begin_marked_content(target, run, transform, shape);
+                    self.content
+                        .paint_ops(run.iter().copied(), transform, shape);
+                    self.content.content_mut().end_marked_content();
+                    rest = tail;
                 }
-                self.content.set_line_join(match style.join {
-                    kurbo::Join::Bevel => pdf_writer::types::LineJoinStyle::BevelJoin,
-                    kurbo::Join::Round => pdf_writer::types::LineJoinStyle::RoundJoin,
-                    kurbo::Join::Miter => pdf_writer::types::LineJoinStyle::MiterJoin,
-                });
-                self.content.set_line_width(style.width as f32);
-                self.content.set_miter_limit(style.miter_limit as f32);
-                self.content.stroke();
             }
-            PaintOpRef::PushLayer { blend: _, alpha: _ } => {}
         }
-        self.content.restore_state();
     }
+
+    /// Begins a marked content sequence for `ops`, as part of `figure` or as an artifact.
+    fn begin_marked_content(
+        &mut self,
+        figure: Option<usize>,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        shape: &impl Shape,
+    ) {
+        use pdf_writer::Name;
+        let page = self.page_transform();
+        let Some(StructNode::Figure { mcids, bbox, .. }) =
+            figure.and_then(|i| self.structure.get_mut(i))
+        else {
+            self.content
+                .content_mut()
+                .begin_marked_content(Name(b"Artifact"));
+            return;
+        };
+        for op in ops {
+            let bounds = page.transform_rect_bbox(paint_bounds(op, transform, shape));
+            *bbox = Some(bbox.map_or(bounds, |bbox| bbox.union(bounds)));
+        }
+        let mcid = self.next_mcid;
+        self.next_mcid += 1;
+        mcids.push((mcid, self.content.group()));
+        self.content
+            .content_mut()
+            .begin_marked_content_with_properties(Name(b"Figure"))
+            .properties()
+            .identify(mcid);
+    }
+}
+
+impl SceneWhisperer for Pdf {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.paint(None, vec![op], transform, shape);
+    }
+
     fn apply_paint_ops<'a, 'b, I>(
         &mut self,
         ops: I,
         transform: Affine,
-        brush_transform: Option<Affine>,
+        _brush_transform: Option<Affine>,
         shape: &impl Shape,
     ) where
         I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
     {
-        for op in ops {
-            self.apply_paint_op(op, transform, brush_transform, shape)
-        }
+        self.paint(None, ops.into_iter().collect(), transform, shape);
+    }
+
+    /// Links become link annotations over what the ops paint, ids named destinations and
+    /// bookmarks entries in the outline of the document.
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        _brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.paint(Some(metadata), ops.into_iter().collect(), transform, shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_contains, fill, push, uncompressed, written};
+    use kurbo::{Circle, Rect, Size};
+    use peniko::{BrushRef, Color, ColorStop, Fill, Gradient};
+
+    #[test]
+    fn tagged_pdf() {
+        let draw = |title: Option<&str>| {
+            let options = PdfOptions {
+                tagged: true,
+                language: Some("en".into()),
+                title: title.map(String::from),
+                ..uncompressed()
+            };
+            let mut pdf = Pdf::with_options(Size::new(20.0, 20.0), 0.1, options);
+            let fill = fill(Color::BLACK);
+            let circle = Circle::new((10.0, 10.0), 5.0);
+            let metadata = Metadata::new()
+                .with_title("A circle")
+                .with_link("https://example.com");
+            pdf.apply_paint_ops_with_metadata(&metadata, [fill], Affine::IDENTITY, None, &circle);
+            pdf.apply_paint_op(fill, Affine::IDENTITY, None, &circle);
+            written(pdf)
+        };
+        let out = draw(Some("Circles"));
+        assert_contains(
+            &out,
+            &[
+                "/MCID 0",
+                "/Artifact BMC",
+                "/Type /StructTreeRoot",
+                "/S /Figure",
+                "/Alt (A circle)",
+                "/S /Link",
+                "/StructParent 1",
+                "/Lang (en)",
+                "/Title (Circles)",
+                "<pdfuaid:part>1</pdfuaid:part>",
+            ],
+        );
+
+        // Without a title the document is still tagged, but doesn't claim PDF/UA.
+        let out = draw(None);
+        assert!(out.contains("/Type /StructTreeRoot"));
+        assert!(!out.contains("pdfuaid:part"));
+    }
+
+    #[test]
+    fn pdf_a_metadata() {
+        let options = PdfOptions {
+            title: Some("Squares & more".into()),
+            author: Some("Ada".into()),
+            keywords: vec!["one".into(), "two".into()],
+            creation_date: Some(PdfDate::new(2024, 3, 1).with_time(12, 30, 0)),
+            pdf_a: true,
+            ..PdfOptions::default()
+        };
+        let square = Rect::new(5.0, 5.0, 15.0, 15.0);
+        let draw = |brush: BrushRef<'_>| {
+            let mut pdf = Pdf::with_options(Size::new(20.0, 20.0), 0.1, options.clone());
+            let fill = PaintOpRef::Fill {
+                style: Fill::NonZero,
+                brush,
+            };
+            pdf.apply_paint_op(fill, Affine::IDENTITY, None, &square);
+            let mut out = Vec::new();
+            pdf.write(&mut out).map(|()| out)
+        };
+        let out = draw((&Color::BLACK).into()).unwrap();
+        assert_eq!(out, draw((&Color::BLACK).into()).unwrap());
+        assert_contains(
+            &String::from_utf8_lossy(&out),
+            &[
+                "/Author (Ada)",
+                "/Keywords (one, two)",
+                "/Producer (selvage)",
+                "/CreationDate (D:20240301123000",
+                "/S /GTS_PDFA1",
+                "/DestOutputProfile",
+                "/ID [",
+                "<pdfaid:part>2</pdfaid:part>",
+                "<pdfaid:conformance>B</pdfaid:conformance>",
+                "<xmp:CreateDate>2024-03-01T12:30:00Z</xmp:CreateDate>",
+                "<rdf:li xml:lang=\"x-default\">Squares &amp; more</rdf:li>",
+            ],
+        );
+        let gradient = Gradient::new_linear((0.0, 0.0), (20.0, 0.0)).with_stops([
+            ColorStop::from((0.0, Color::BLACK)),
+            ColorStop::from((1.0, Color::WHITE)),
+        ]);
+        let err = draw((&gradient).into()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&PdfAViolation::Gradient)
+        );
+    }
+
+    #[test]
+    fn pdf_compression_and_dedup() {
+        let draw = |compression_level| {
+            let options = PdfOptions {
+                compression_level,
+                ..PdfOptions::default()
+            };
+            let mut pdf = Pdf::with_options(Size::new(100.0, 100.0), 0.1, options);
+            let fill = fill(Color::BLACK);
+            // Each layer is a transparency group sharing the graphics state of its alpha.
+            for i in 0..10 {
+                let page = Rect::new(0.0, 0.0, 100.0, 100.0);
+                pdf.apply_paint_op(push(0.5), Affine::IDENTITY, None, &page);
+                for j in 0..100 {
+                    let offset = kurbo::Vec2::new(j as f64, (i * 10) as f64);
+                    let square = Rect::new(0.0, 0.0, 1.0, 1.0) + offset;
+                    pdf.apply_paint_op(fill, Affine::IDENTITY, None, &square);
+                }
+                pdf.apply_paint_op(PaintOpRef::PopLayer, Affine::IDENTITY, None, &page);
+            }
+            written(pdf)
+        };
+        let compressed = draw(6);
+        let uncompressed = draw(0);
+        assert!(compressed.len() * 2 < uncompressed.len());
+        assert!(compressed.contains("/Filter /FlateDecode"));
+        assert!(compressed.contains("/GS0"));
+        assert!(!compressed.contains("/GS1"));
+    }
+
+    #[test]
+    fn pdf_navigation() {
+        let mut pdf = Pdf::with_options(Size::new(100.0, 100.0), 0.1, uncompressed());
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let fill = fill(Color::BLACK);
+        let push = PaintOpRef::PushLayer {
+            blend: peniko::Mix::Clip.into(),
+            alpha: 1.0,
+        };
+        let parts = Metadata::new().with_bookmark("Parts");
+        let page = Rect::new(0.0, 0.0, 100.0, 100.0);
+        pdf.apply_paint_ops_with_metadata(&parts, [push], Affine::IDENTITY, None, &page);
+        let bolt = Metadata::new().with_id("bolt").with_bookmark("Bolt");
+        let at = Affine::translate((20.0, 30.0));
+        pdf.apply_paint_ops_with_metadata(&bolt, [fill], at, None, &square);
+        pdf.apply_paint_op(PaintOpRef::PopLayer, Affine::IDENTITY, None, &page);
+        let link = Metadata::new().with_link("#bolt");
+        let rotated = Affine::translate((50.0, 50.0)) * Affine::rotate(0.5);
+        pdf.apply_paint_ops_with_metadata(&link, [fill], rotated, None, &square);
+        let link = Metadata::new().with_link("https://example.com/spec");
+        pdf.apply_paint_ops_with_metadata(&link, [fill], at, None, &square);
+        let out = written(pdf);
+        assert_contains(
+            &out,
+            &[
+                "/S /GoTo",
+                "/D (bolt)",
+                "/URI (https://example.com/spec)",
+                "/QuadPoints [",
+                "/Names [(bolt) ",
+                "/PageMode /UseOutlines",
+                "/Title (Parts)",
+                "/Title (Bolt)",
+                "/XYZ 20 70 0",
+                "/Count 2",
+                "/Count 1",
+            ],
+        );
+        assert_eq!(out.matches("/QuadPoints").count(), 1);
     }
 }
@@ -0,0 +1,243 @@
+use crate::metadata::Metadata;
+use crate::offset::StrokeAlignment;
+use crate::shape::StaticShape;
+use crate::whisperer::{apply_paint_ops_with, PaintOpRef};
+use crate::SceneWhisperer;
+use kurbo::{Affine, PathEl, Point, Rect, Shape};
+
+/// Snaps axis aligned edges to the device pixel grid so they render crisply.
+///
+/// Fills and inside or outside aligned strokes get their edges moved to pixel boundaries,
+/// while centered strokes are moved so that their edges fall on pixel boundaries for their
+/// width rounded to whole pixels, e.g. 1px lines go through pixel centers.
+/// Segments which aren't horizontal or vertical in device space are left alone.
+pub struct PixelSnap<'a, T: SceneWhisperer> {
+    whisperer: &'a mut T,
+    device_transform: Affine,
+    tolerance: f64,
+}
+
+impl<'w, T: SceneWhisperer> PixelSnap<'w, T> {
+    /// `device_transform` maps the coordinates given to the whisperer to device pixels.
+    pub fn new(whisperer: &'w mut T, device_transform: Affine, tolerance: f64) -> Self {
+        Self {
+            whisperer,
+            device_transform,
+            tolerance,
+        }
+    }
+
+    /// The offset from whole pixels that the edges painted by `op` should be snapped to.
+    fn pixel_offset(&self, op: &PaintOpRef<'_, '_>, transform: Affine) -> Option<f64> {
+        match op {
+            PaintOpRef::Fill { .. } | PaintOpRef::PushLayer { .. } => Some(0.0),
+            PaintOpRef::Stroke {
+                style, alignment, ..
+            } => {
+                if *alignment != StrokeAlignment::Center {
+                    return Some(0.0);
+                }
+                let scale = (self.device_transform * transform)
+                    .determinant()
+                    .abs()
+                    .sqrt();
+                let width = (style.width * scale).round().max(1.0);
+                Some(if width % 2.0 == 1.0 { 0.5 } else { 0.0 })
+            }
+            PaintOpRef::PopLayer => None,
+        }
+    }
+
+    /// The shape with its axis aligned edges snapped, or `None` if nothing moved.
+    fn snap_shape(
+        &self,
+        shape: &impl Shape,
+        transform: Affine,
+        offset: f64,
+    ) -> Option<StaticShape> {
+        let to_device = self.device_transform * transform;
+        if to_device.determinant() == 0.0 {
+            return None;
+        }
+        let from_device = to_device.inverse();
+        let snap = |x: f64| (x - offset).round() + offset;
+        let [a, b, c, d, _, _] = to_device.as_coeffs();
+        let axis_aligned = (b == 0.0 && c == 0.0) || (a == 0.0 && d == 0.0);
+        let rect = shape
+            .as_rect()
+            .or_else(|| shape.as_rounded_rect().map(|r| r.rect()));
+        if let (Some(rect), true) = (rect, axis_aligned) {
+            let device = to_device.transform_rect_bbox(rect);
+            let snapped = Rect::new(
+                snap(device.x0),
+                snap(device.y0),
+                snap(device.x1),
+                snap(device.y1),
+            );
+            if snapped == device {
+                return None;
+            }
+            let rect = from_device.transform_rect_bbox(snapped);
+            return Some(match shape.as_rounded_rect() {
+                Some(r) => StaticShape::RoundedRect(rect.to_rounded_rect(r.radii())),
+                None => StaticShape::Rect(rect),
+            });
+        }
+
+        let mut path = to_device * shape.to_path(self.tolerance);
+        let mut moved = false;
+        let els = path.elements_mut();
+        let mut start = 0;
+        for i in 0..els.len() {
+            let (prev, p) = match els[i] {
+                PathEl::MoveTo(_) => {
+                    start = i;
+                    continue;
+                }
+                PathEl::LineTo(p) => (i - 1, p),
+                PathEl::ClosePath => match els[start] {
+                    PathEl::MoveTo(p) => (i - 1, p),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let Some(p0) = els[prev].end_point() else {
+                continue;
+            };
+            let end = if matches!(els[i], PathEl::ClosePath) {
+                start
+            } else {
+                i
+            };
+            let (dx, dy) = ((p.x - p0.x).abs(), (p.y - p0.y).abs());
+            let snapped = if dy <= self.tolerance && dx > dy {
+                let y = snap((p0.y + p.y) / 2.0);
+                Some((Point::new(p0.x, y), Point::new(p.x, y)))
+            } else if dx <= self.tolerance && dy > dx {
+                let x = snap((p0.x + p.x) / 2.0);
+                Some((Point::new(x, p0.y), Point::new(x, p.y)))
+            } else {
+                None
+            };
+            if let Some((q0, q)) = snapped {
+                moved |= q0 != p0 || q != p;
+                set_end_point(&mut els[prev], q0);
+                set_end_point(&mut els[end], q);
+            }
+        }
+        if !moved {
+            return None;
+        }
+        path.apply_affine(from_device);
+        Some(StaticShape::BezPath(path))
+    }
+
+    fn snap_ops<'a, 'b>(
+        &mut self,
+        mut metadata: Option<&Metadata>,
+        ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let mut ops = ops.into_iter().peekable();
+        while let Some(op) = ops.next() {
+            let offset = self.pixel_offset(&op, transform);
+            let mut batch = vec![op];
+            while let Some(next) = ops.next_if(|next| self.pixel_offset(next, transform) == offset)
+            {
+                batch.push(next);
+            }
+            let metadata = metadata.take();
+            match offset.and_then(|offset| self.snap_shape(shape, transform, offset)) {
+                Some(snapped) => apply_paint_ops_with(
+                    self.whisperer,
+                    metadata,
+                    batch,
+                    transform,
+                    brush_transform,
+                    &snapped,
+                ),
+                None => apply_paint_ops_with(
+                    self.whisperer,
+                    metadata,
+                    batch,
+                    transform,
+                    brush_transform,
+                    shape,
+                ),
+            }
+        }
+    }
+}
+
+fn set_end_point(el: &mut PathEl, p: Point) {
+    match el {
+        PathEl::MoveTo(q) | PathEl::LineTo(q) => *q = p,
+        PathEl::QuadTo(_, q) => *q = p,
+        PathEl::CurveTo(_, _, q) => *q = p,
+        PathEl::ClosePath => (),
+    }
+}
+
+impl<'w, T: SceneWhisperer> SceneWhisperer for PixelSnap<'w, T> {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.apply_paint_ops([op], transform, brush_transform, shape);
+    }
+
+    /// Consecutive ops which snap to the same offset are passed on together.
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.snap_ops(None, ops, transform, brush_transform, shape);
+    }
+
+    /// The metadata goes with the first batch of ops, so ids stay unique.
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.snap_ops(Some(metadata), ops, transform, brush_transform, shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fill, stroke};
+    use crate::DisplayList;
+    use kurbo::Stroke;
+    use peniko::Color;
+
+    #[test]
+    fn pixel_snap_rect() {
+        let mut list = DisplayList::new();
+        let mut snap = PixelSnap::new(&mut list, Affine::scale(2.0), 1e-6);
+        let rect = Rect::new(0.2, 0.2, 10.1, 10.1);
+        snap.apply_paint_op(fill(Color::BLACK), Affine::IDENTITY, None, &rect);
+        let style = Stroke::new(0.5);
+        let stroke = stroke(&style, Color::BLACK, StrokeAlignment::Center);
+        snap.apply_paint_op(stroke, Affine::IDENTITY, None, &rect);
+        let rects: Vec<_> = list.symbols.iter().map(|s| s.shape.as_rect()).collect();
+        assert_eq!(rects[0], Some(Rect::new(0.0, 0.0, 10.0, 10.0)));
+        assert_eq!(rects[1], Some(Rect::new(0.25, 0.25, 10.25, 10.25)));
+    }
+}
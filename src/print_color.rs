@@ -0,0 +1,283 @@
+use peniko::Color;
+use serde::{Deserialize, Serialize};
+
+/// A color made of process inks or light, with components from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProcessColor {
+    /// An sRGB color.
+    Rgb([f32; 3]),
+    Cmyk([f32; 4]),
+    /// A color in the wider gamut of Display P3, which has the primaries of DCI-P3 with the
+    /// white point and transfer function of sRGB.
+    ///
+    /// Brushes are always sRGB, so Display P3 is only painted as a print color. PDF output
+    /// keeps it in an ICC based color space, other backends get it clipped to sRGB.
+    DisplayP3([f32; 3]),
+}
+
+impl ProcessColor {
+    /// The color at `tint`, from white at 0 to the full color at 1.
+    pub fn tinted(self, tint: f32) -> Self {
+        match self {
+            ProcessColor::Rgb(rgb) => ProcessColor::Rgb(rgb.map(|x| 1.0 - tint * (1.0 - x))),
+            ProcessColor::Cmyk(cmyk) => ProcessColor::Cmyk(cmyk.map(|x| x * tint)),
+            ProcessColor::DisplayP3(rgb) => {
+                ProcessColor::DisplayP3(rgb.map(|x| 1.0 - tint * (1.0 - x)))
+            }
+        }
+    }
+
+    /// A naive conversion for CMYK, without a profile for the inks. Display P3 colors are
+    /// converted exactly, and clipped to the sRGB gamut.
+    pub fn to_srgb(self) -> Color {
+        let [r, g, b] = match self {
+            ProcessColor::Rgb(rgb) => rgb,
+            ProcessColor::Cmyk([c, m, y, k]) => [c, m, y].map(|x| (1.0 - x) * (1.0 - k)),
+            ProcessColor::DisplayP3(rgb) => {
+                let linear = rgb.map(|x| srgb_decode(x as f64));
+                P3_TO_SRGB.map(|row| {
+                    let x = (0..3).map(|i| row[i] * linear[i]).sum::<f64>();
+                    srgb_encode(x.clamp(0.0, 1.0)) as f32
+                })
+            }
+        };
+        let [r, g, b] = [r, g, b].map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8);
+        Color::rgb8(r, g, b)
+    }
+}
+
+/// Converts linear Display P3 to linear sRGB, both have the D65 white point.
+const P3_TO_SRGB: [[f64; 3]; 3] = [
+    [1.2249401762805598, -0.22494017628055996, 0.0],
+    [-0.04205695470968816, 1.042056954709688, 0.0],
+    [
+        -0.019637554590334432,
+        -0.07863604555063188,
+        1.0982736001409663,
+    ],
+];
+
+/// Converts an encoded sRGB component to linear light.
+pub(crate) fn srgb_decode(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_encode(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// A color for print or wide gamut displays, which backends that can't show it approximate
+/// in sRGB.
+///
+/// Print colors are given in [`Metadata`](crate::Metadata) for the solid brushes of the ops
+/// painted with it, they aren't brushes themselves.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PrintColor {
+    Process(ProcessColor),
+    /// An ink of its own, printed on a separate plate.
+    Spot {
+        /// The name of the ink, as the printer knows it, e.g. `"PANTONE 185 C"`.
+        name: String,
+        /// How much of the ink is applied, from 0 to 1.
+        tint: f32,
+        /// The color of the ink at full tint, for devices without it.
+        alternate: ProcessColor,
+    },
+}
+
+impl PrintColor {
+    pub fn cmyk(c: f32, m: f32, y: f32, k: f32) -> Self {
+        PrintColor::Process(ProcessColor::Cmyk([c, m, y, k]))
+    }
+
+    pub fn display_p3(r: f32, g: f32, b: f32) -> Self {
+        PrintColor::Process(ProcessColor::DisplayP3([r, g, b]))
+    }
+
+    /// The spot color `name` at full tint.
+    pub fn spot(name: impl Into<String>, alternate: ProcessColor) -> Self {
+        PrintColor::Spot {
+            name: name.into(),
+            tint: 1.0,
+            alternate,
+        }
+    }
+
+    /// The same color at `tint`, process colors are mixed with white.
+    pub fn with_tint(self, tint: f32) -> Self {
+        match self {
+            PrintColor::Process(color) => PrintColor::Process(color.tinted(tint)),
+            PrintColor::Spot {
+                name, alternate, ..
+            } => PrintColor::Spot {
+                name,
+                tint,
+                alternate,
+            },
+        }
+    }
+
+    /// The approximation used for the brushes of ops painted in this color.
+    pub fn to_srgb(&self) -> Color {
+        match self {
+            PrintColor::Process(color) => color.to_srgb(),
+            PrintColor::Spot {
+                tint, alternate, ..
+            } => alternate.tinted(*tint).to_srgb(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "pdf"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_contains, fill_op, stroke_op, symbol, uncompressed};
+    use crate::{DisplayList, Metadata, Pdf, PdfAViolation, PdfOptions, StrokeAlignment};
+    use kurbo::{Affine, Rect, Size};
+
+    #[test]
+    fn pdf_print_colors() {
+        let spot = PrintColor::spot("PANTONE 185 C", ProcessColor::Cmyk([0.0, 0.9, 0.8, 0.0]))
+            .with_tint(0.5);
+        let black = PrintColor::cmyk(0.0, 0.0, 0.0, 1.0);
+        assert_eq!(spot.to_srgb(), Color::rgb8(255, 140, 153));
+        let metadata = Metadata::new()
+            .with_fill_color(spot.clone())
+            .with_stroke_color(black.clone())
+            .with_overprint();
+        // The brushes are placeholders, replaying takes them from the print colors.
+        let paint_ops = vec![
+            fill_op(Color::WHITE),
+            stroke_op(1.0, Color::WHITE, StrokeAlignment::Inside),
+        ];
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let symbol = crate::Symbol {
+            metadata: Some(metadata),
+            ..symbol(square, paint_ops, Affine::IDENTITY)
+        };
+        let mut list = DisplayList::new();
+        list.push(symbol.clone());
+        list.push(symbol);
+        let draw = |pdf_a| {
+            let options = PdfOptions {
+                pdf_a,
+                ..uncompressed()
+            };
+            let mut pdf = Pdf::with_options(Size::new(10.0, 10.0), 0.1, options);
+            list.replay(&mut pdf);
+            let mut out = Vec::new();
+            pdf.write(&mut out).map(|()| out)
+        };
+        let out = draw(false).unwrap();
+        let out = String::from_utf8_lossy(&out);
+        assert_contains(
+            &out,
+            &[
+                "/Separation /PANTONE#20185#20C /DeviceCMYK",
+                "/C1 [0 0.9 0.8 0]",
+                "/CS0 cs\n0.5 scn",
+                "0 0 0 1 K",
+                "/OP true",
+                "/op true",
+                "/OPM 1",
+                "/GS0 gs",
+            ],
+        );
+        assert_eq!(out.matches("/Separation").count(), 1);
+
+        #[cfg(feature = "svg")]
+        {
+            let mut svg = crate::Svg::new(Size::new(10.0, 10.0));
+            list.replay(&mut svg);
+            let out = svg.display().to_string();
+            assert_eq!(black.to_srgb(), Color::BLACK);
+            assert_contains(&out, &["fill=\"#ff8c99\"", "stroke=\"#000000\""]);
+            assert!(!out.contains("#ffffff"), "{out}");
+
+            // Translucent brushes stay translucent.
+            let translucent = crate::Symbol {
+                metadata: Some(Metadata::new().with_fill_color(black)),
+                ..crate::test_util::symbol(
+                    square,
+                    vec![fill_op(Color::rgba8(255, 255, 255, 128))],
+                    Affine::IDENTITY,
+                )
+            };
+            let mut svg = crate::Svg::new(Size::new(10.0, 10.0));
+            translucent.apply(&mut svg);
+            let out = svg.display().to_string();
+            assert_contains(
+                &out,
+                &["fill=\"#000000\"", "fill-opacity=\"0.5019607843137255\""],
+            );
+        }
+        let err = draw(true).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(&PdfAViolation::Cmyk)
+        );
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn color_management() {
+        use crate::test_util::{fill, written};
+        use crate::{PaintOpRef, SceneWhisperer, Svg, SvgOptions};
+        use peniko::{ColorStop, Fill, Gradient};
+        let p3 = PrintColor::display_p3(1.0, 0.0, 0.0);
+        assert_eq!(p3.to_srgb(), Color::rgb8(255, 0, 0));
+        let gray = PrintColor::display_p3(0.6, 0.6, 0.6);
+        assert_eq!(gray.to_srgb(), Color::rgb8(153, 153, 153));
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let options = PdfOptions {
+            pdf_a: true,
+            ..uncompressed()
+        };
+        let mut pdf = Pdf::with_options(Size::new(10.0, 10.0), 0.1, options);
+        pdf.apply_paint_op(fill(Color::BLACK), Affine::IDENTITY, None, &square);
+        let metadata = Metadata::new().with_fill_color(p3.clone());
+        let ops = [fill(p3.to_srgb())];
+        pdf.apply_paint_ops_with_metadata(&metadata, ops, Affine::IDENTITY, None, &square);
+        let out = written(pdf);
+        assert_contains(
+            &out,
+            &["/DefaultRGB", "/P3 cs\n1 0 0 scn", "/DestOutputProfile"],
+        );
+        // The output intent shares the profile of the DefaultRGB color space.
+        assert_eq!(out.matches("/ICCBased").count(), 2);
+        assert_eq!(out.matches("/N 3").count(), 2);
+
+        let gradient = Gradient::new_linear((0.0, 0.0), (10.0, 0.0))
+            .with_extend(peniko::Extend::Reflect)
+            .with_stops([
+                ColorStop::from((0.0, Color::BLACK)),
+                ColorStop::from((1.0, Color::WHITE)),
+            ]);
+        let mut svg = Svg::with_options(Size::new(10.0, 10.0), SvgOptions::minified(2));
+        let fill = PaintOpRef::Fill {
+            style: Fill::NonZero,
+            brush: (&gradient).into(),
+        };
+        svg.apply_paint_op(fill, Affine::IDENTITY, Some(Affine::scale(2.0)), &square);
+        assert_contains(
+            &svg.display().to_string(),
+            &[
+                "<linearGradient",
+                "color-interpolation=\"sRGB\"",
+                "gradientUnits=\"userSpaceOnUse\"",
+                "gradientTransform=\"matrix(2 0 0 2 0 0)\"",
+                "spreadMethod=\"reflect\"",
+                "<stop offset=\"1\" stop-color=\"#ffffff\"/>",
+                "fill=\"url(#a)\"",
+            ],
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use kurbo::{Affine, Rect, Shape};
+use crate::offset::offset_path;
+use kurbo::{Affine, Circle, Join, Rect, Shape};
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum StaticShape {
     PathSeg(kurbo::PathSeg),
     Arc(kurbo::Arc),
@@ -127,9 +128,63 @@ impl Shape for StaticShape {
             S::RoundedRect(it) => it.bounding_box(),
         }
     }
+
+    fn as_line(&self) -> Option<kurbo::Line> {
+        match self {
+            StaticShape::Line(it) => Some(*it),
+            _ => None,
+        }
+    }
+
+    fn as_rect(&self) -> Option<Rect> {
+        match self {
+            StaticShape::Rect(it) => Some(*it),
+            _ => None,
+        }
+    }
+
+    fn as_rounded_rect(&self) -> Option<kurbo::RoundedRect> {
+        match self {
+            StaticShape::RoundedRect(it) => Some(*it),
+            _ => None,
+        }
+    }
+
+    fn as_circle(&self) -> Option<Circle> {
+        match self {
+            StaticShape::Circle(it) => Some(*it),
+            _ => None,
+        }
+    }
+
+    fn as_path_slice(&self) -> Option<&[kurbo::PathEl]> {
+        match self {
+            StaticShape::BezPath(it) => Some(it.elements()),
+            _ => None,
+        }
+    }
 }
 
 impl StaticShape {
+    /// Captures any shape, keeping its kind when `Shape` exposes it and otherwise
+    /// converting it to a `BezPath` with `tolerance`.
+    pub fn from_shape(shape: &impl Shape, tolerance: f64) -> Self {
+        use StaticShape as S;
+        if let Some(it) = shape.as_line() {
+            S::Line(it)
+        } else if let Some(it) = shape.as_rect() {
+            S::Rect(it)
+        } else if let Some(it) = shape.as_rounded_rect() {
+            S::RoundedRect(it)
+        } else if let Some(it) = shape.as_circle() {
+            S::Circle(it)
+        } else if let Some(it) = shape.as_path_slice() {
+            S::BezPath(kurbo::BezPath::from_vec(it.to_vec()))
+        } else {
+            S::BezPath(shape.to_path(tolerance))
+        }
+    }
+
     pub fn apply_transform(self, transform: Affine, tolerance: f64) -> Self {
         use StaticShape as S;
         match self {
@@ -146,4 +201,67 @@ impl StaticShape {
             S::RoundedRect(it) => S::BezPath(transform * it.to_path(tolerance)),
         }
     }
+
+    /// Offsets the outline of the shape by `distance`, outward for positive distances
+    /// and inward for negative ones. See [`offset_path`] for the details.
+    ///
+    /// Circles and rectangles whose offsets are exactly representable keep their kind,
+    /// everything else becomes a `BezPath`. Circles, ellipses and rectangles shrunk by
+    /// half their smaller side or more become an empty path.
+    pub fn offset(&self, distance: f64, join: Join, miter_limit: f64, tolerance: f64) -> Self {
+        use StaticShape as S;
+        // Half the smaller side of shapes which have a middle.
+        let inradius = match self {
+            S::Circle(it) => Some(it.radius),
+            S::Ellipse(it) => Some(it.radii().x.abs().min(it.radii().y.abs())),
+            S::Rect(it) => Some(it.width().abs().min(it.height().abs()) / 2.0),
+            S::RoundedRect(it) => Some(it.width().abs().min(it.height().abs()) / 2.0),
+            _ => None,
+        };
+        // A shape shrunk past its middle covers nothing.
+        if inradius.is_some_and(|inradius| -distance >= inradius) {
+            return S::BezPath(kurbo::BezPath::new());
+        }
+        match self {
+            S::Circle(it) => S::Circle(Circle::new(it.center, it.radius + distance)),
+            S::Rect(it)
+                if distance < 0.0
+                    || (join == Join::Miter && miter_limit > std::f64::consts::SQRT_2) =>
+            {
+                S::Rect(it.abs().inflate(distance, distance))
+            }
+            _ => S::BezPath(offset_path(self, distance, join, miter_limit, tolerance)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurbo::{Ellipse, RoundedRect};
+
+    #[test]
+    fn offset_circle_past_zero() {
+        let circle = StaticShape::Circle(Circle::new((5.0, 5.0), 2.0));
+        let shrunk = circle.offset(-1.0, Join::Round, 4.0, 1e-3);
+        assert!(matches!(shrunk, StaticShape::Circle(c) if c.radius == 1.0));
+        let gone = circle.offset(-3.0, Join::Round, 4.0, 1e-3);
+        assert!(gone.path_elements(1e-3).next().is_none());
+        assert_eq!(gone.area(), 0.0);
+    }
+
+    #[test]
+    fn offset_past_middle() {
+        let rect = StaticShape::Rect(Rect::new(0.0, 0.0, 10.0, 10.0));
+        let shrunk = rect.offset(-4.0, Join::Miter, 4.0, 1e-3);
+        assert!(matches!(shrunk, StaticShape::Rect(r) if r == Rect::new(4.0, 4.0, 6.0, 6.0)));
+        for shape in [
+            rect,
+            StaticShape::RoundedRect(RoundedRect::new(0.0, 0.0, 20.0, 10.0, 2.0)),
+            StaticShape::Ellipse(Ellipse::new((5.0, 5.0), (5.0, 8.0), 0.5)),
+        ] {
+            let gone = shape.offset(-6.0, Join::Miter, 4.0, 1e-3);
+            assert!(gone.path_elements(1e-3).next().is_none(), "{shape:?}");
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::metadata::Metadata;
 use crate::shape::StaticShape;
 use crate::whisperer::PaintOpRef;
 use crate::SceneWhisperer;
@@ -82,4 +83,23 @@ impl<'w, T: SceneWhisperer> SceneWhisperer for ShapeTransform<'w, T> {
         self.whisperer
             .apply_paint_ops(ops, transform, brush_transform, shape);
     }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.whisperer.apply_paint_ops_with_metadata(
+            metadata,
+            ops,
+            transform,
+            brush_transform,
+            shape,
+        );
+    }
 }
@@ -0,0 +1,376 @@
+use crate::cull::paint_bounds;
+use crate::display_list::{DisplayList, Symbol};
+use crate::whisperer::PaintOp;
+use kurbo::{Point, Rect};
+use std::collections::BTreeSet;
+
+/// The most entries in a node of the tree, before it is split.
+const MAX_ENTRIES: usize = 16;
+
+/// The number of symbols from which display lists index themselves for culling and hit
+/// testing, scanning fewer is about as quick.
+pub(crate) const INDEX_THRESHOLD: usize = 512;
+
+#[derive(Clone, Debug)]
+enum Node {
+    Leaf(Vec<(Rect, usize)>),
+    Branch(Vec<(Rect, Node)>),
+}
+
+/// An R-tree over the painted bounds of the symbols of a display list.
+///
+/// Symbols are identified by their index in `DisplayList::symbols`, so an index has to be
+/// updated when symbols are inserted or removed anywhere but the end of the list.
+/// Symbols with layer ops are kept apart and returned by every query, since skipping them
+/// would unbalance the layers.
+#[derive(Clone, Debug)]
+pub struct SpatialIndex {
+    root: Node,
+    layers: BTreeSet<usize>,
+    len: usize,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self {
+            root: Node::Leaf(Vec::new()),
+            layers: BTreeSet::new(),
+            len: 0,
+        }
+    }
+}
+
+fn has_layer_ops(symbol: &Symbol) -> bool {
+    symbol
+        .paint_ops
+        .iter()
+        .any(|op| matches!(op, PaintOp::PushLayer { .. } | PaintOp::PopLayer))
+}
+
+/// A conservative bounding box of everything painted by the fill and stroke ops of `symbol`,
+/// `None` if it paints nothing or its bounds aren't finite.
+fn symbol_bounds(symbol: &Symbol) -> Option<Rect> {
+    symbol
+        .paint_ops
+        .iter()
+        .filter(|op| matches!(op, PaintOp::Fill { .. } | PaintOp::Stroke { .. }))
+        .map(|op| paint_bounds(&op.into(), symbol.transform, &symbol.shape))
+        .reduce(|a, b| a.union(b))
+        .filter(|bounds| bounds.is_finite())
+}
+
+fn intersects(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
+
+fn contains(outer: Rect, inner: Rect) -> bool {
+    outer.x0 <= inner.x0 && outer.y0 <= inner.y0 && outer.x1 >= inner.x1 && outer.y1 >= inner.y1
+}
+
+fn union<T>(entries: &[(Rect, T)]) -> Rect {
+    entries
+        .iter()
+        .map(|(rect, _)| *rect)
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO)
+}
+
+/// Splits `entries` in half along the axis their centers are most spread out on,
+/// returning the second half.
+fn split<T>(entries: &mut Vec<(Rect, T)>) -> Vec<(Rect, T)> {
+    let spread = |axis: fn(Point) -> f64| {
+        let (min, max) = entries
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), (r, _)| {
+                (min.min(axis(r.center())), max.max(axis(r.center())))
+            });
+        max - min
+    };
+    if spread(|p| p.x) >= spread(|p| p.y) {
+        entries.sort_by(|(a, _), (b, _)| a.center().x.total_cmp(&b.center().x));
+    } else {
+        entries.sort_by(|(a, _), (b, _)| a.center().y.total_cmp(&b.center().y));
+    }
+    entries.split_off(entries.len() / 2)
+}
+
+/// Groups `entries` into nodes of at most `MAX_ENTRIES` using sort-tile-recursive packing.
+fn pack<T>(mut entries: Vec<(Rect, T)>, node: fn(Vec<(Rect, T)>) -> Node) -> Vec<(Rect, Node)> {
+    let nodes = entries.len().div_ceil(MAX_ENTRIES);
+    let slices = (nodes as f64).sqrt().ceil() as usize;
+    let slice_len = slices * MAX_ENTRIES;
+    entries.sort_by(|(a, _), (b, _)| a.center().x.total_cmp(&b.center().x));
+    let mut out = Vec::with_capacity(nodes);
+    while !entries.is_empty() {
+        let rest = entries.split_off(slice_len.min(entries.len()));
+        let mut slice = std::mem::replace(&mut entries, rest);
+        slice.sort_by(|(a, _), (b, _)| a.center().y.total_cmp(&b.center().y));
+        while !slice.is_empty() {
+            let rest = slice.split_off(MAX_ENTRIES.min(slice.len()));
+            let group = std::mem::replace(&mut slice, rest);
+            out.push((union(&group), node(group)));
+        }
+    }
+    out
+}
+
+impl Node {
+    fn bounds(&self) -> Rect {
+        match self {
+            Node::Leaf(entries) => union(entries),
+            Node::Branch(children) => union(children),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Node::Leaf(entries) => entries.is_empty(),
+            Node::Branch(children) => children.is_empty(),
+        }
+    }
+
+    /// Inserts an entry, returning a new sibling if the node had to be split.
+    fn insert(&mut self, rect: Rect, id: usize) -> Option<Node> {
+        match self {
+            Node::Leaf(entries) => {
+                entries.push((rect, id));
+                (entries.len() > MAX_ENTRIES).then(|| Node::Leaf(split(entries)))
+            }
+            Node::Branch(children) => {
+                // The child which grows the least, preferring smaller children.
+                let cost =
+                    |bounds: Rect| (bounds.union(rect).area() - bounds.area(), bounds.area());
+                let (i, _) = children
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, (a, _)), (_, (b, _))| {
+                        let ((a_growth, a_area), (b_growth, b_area)) = (cost(*a), cost(*b));
+                        a_growth
+                            .total_cmp(&b_growth)
+                            .then(a_area.total_cmp(&b_area))
+                    })?;
+                let (bounds, child) = &mut children[i];
+                *bounds = bounds.union(rect);
+                if let Some(sibling) = child.insert(rect, id) {
+                    *bounds = child.bounds();
+                    children.push((sibling.bounds(), sibling));
+                }
+                (children.len() > MAX_ENTRIES).then(|| Node::Branch(split(children)))
+            }
+        }
+    }
+
+    fn remove(&mut self, rect: Rect, id: usize) -> bool {
+        match self {
+            Node::Leaf(entries) => match entries.iter().position(|(_, x)| *x == id) {
+                Some(i) => {
+                    entries.swap_remove(i);
+                    true
+                }
+                None => false,
+            },
+            Node::Branch(children) => {
+                for i in 0..children.len() {
+                    let (bounds, child) = &mut children[i];
+                    if contains(*bounds, rect) && child.remove(rect, id) {
+                        if child.is_empty() {
+                            children.swap_remove(i);
+                        } else {
+                            *bounds = child.bounds();
+                        }
+                        return true;
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    fn query(&self, rect: Rect, out: &mut Vec<usize>) {
+        match self {
+            Node::Leaf(entries) => out.extend(
+                entries
+                    .iter()
+                    .filter(|(bounds, _)| intersects(*bounds, rect))
+                    .map(|(_, id)| *id),
+            ),
+            Node::Branch(children) => {
+                for (bounds, child) in children {
+                    if intersects(*bounds, rect) {
+                        child.query(rect, out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bulk loads an index over every symbol of `display_list`.
+    pub fn build(display_list: &DisplayList) -> Self {
+        let mut layers = BTreeSet::new();
+        let mut entries = Vec::new();
+        for (i, symbol) in display_list.symbols.iter().enumerate() {
+            if has_layer_ops(symbol) {
+                layers.insert(i);
+            } else if let Some(bounds) = symbol_bounds(symbol) {
+                entries.push((bounds, i));
+            }
+        }
+        let len = entries.len() + layers.len();
+        let mut nodes = pack(entries, Node::Leaf);
+        while nodes.len() > 1 {
+            nodes = pack(nodes, Node::Branch);
+        }
+        let root = nodes.pop().map_or(Node::Leaf(Vec::new()), |(_, node)| node);
+        Self { root, layers, len }
+    }
+
+    /// The number of symbols in the index, not counting symbols which paint nothing.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds `symbol` under `index`.
+    pub fn insert(&mut self, index: usize, symbol: &Symbol) {
+        if has_layer_ops(symbol) {
+            self.layers.insert(index);
+        } else if let Some(bounds) = symbol_bounds(symbol) {
+            if let Some(sibling) = self.root.insert(bounds, index) {
+                let root = std::mem::replace(&mut self.root, Node::Branch(Vec::new()));
+                self.root = Node::Branch(vec![(root.bounds(), root), (sibling.bounds(), sibling)]);
+            }
+        } else {
+            return;
+        }
+        self.len += 1;
+    }
+
+    /// Removes `symbol`, which must be the same as when it was inserted under `index`.
+    /// Returns whether it was found.
+    pub fn remove(&mut self, index: usize, symbol: &Symbol) -> bool {
+        let removed = if has_layer_ops(symbol) {
+            self.layers.remove(&index)
+        } else if let Some(bounds) = symbol_bounds(symbol) {
+            let removed = self.root.remove(bounds, index);
+            if let Node::Branch(children) = &mut self.root {
+                if children.len() == 1 {
+                    self.root = children.pop().unwrap().1;
+                } else if children.is_empty() {
+                    self.root = Node::Leaf(Vec::new());
+                }
+            }
+            removed
+        } else {
+            false
+        };
+        self.len -= removed as usize;
+        removed
+    }
+
+    /// The symbols which may paint within `rect`, in paint order.
+    ///
+    /// Bounds are conservative, and symbols with layer ops are always included.
+    pub fn query_rect(&self, rect: Rect) -> Vec<usize> {
+        let mut out: Vec<usize> = self.layers.iter().copied().collect();
+        self.root.query(rect, &mut out);
+        out.sort_unstable();
+        out
+    }
+
+    /// The symbols which may paint at `point`, in paint order.
+    pub fn query_point(&self, point: Point) -> Vec<usize> {
+        self.query_rect(Rect::from_points(point, point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fill_op, symbol};
+    use crate::Hit;
+    use kurbo::Affine;
+    use peniko::Color;
+
+    #[test]
+    fn spatial_index_queries() {
+        let mut list = DisplayList::new();
+        for i in 0..2000 {
+            let (x, y) = ((i % 50) as f64 * 10.0, (i / 50) as f64 * 10.0);
+            let square = Rect::new(0.0, 0.0, 5.0, 5.0);
+            let transform = Affine::translate((x, y));
+            list.push(symbol(square, vec![fill_op(Color::BLACK)], transform));
+        }
+        let mut index = SpatialIndex::build(&list);
+        assert_eq!(index.len(), 2000);
+        assert_eq!(index.query_point(Point::new(12.0, 3.0)), vec![1]);
+        assert_eq!(index.query_rect(Rect::new(0.0, 0.0, 12.0, 12.0)).len(), 4);
+        assert!(index.remove(1, &list.symbols[1]));
+        assert!(index.query_point(Point::new(12.0, 3.0)).is_empty());
+        index.insert(1, &list.symbols[1]);
+        assert_eq!(index.query_point(Point::new(12.0, 3.0)), vec![1]);
+        let point = Point::new(12.0, 3.0);
+        assert_eq!(
+            list.hit_test_in(&index, point, 0.0),
+            vec![Hit { symbol: 1, op: 0 }]
+        );
+        assert_eq!(
+            list.hit_test(point, 0.0),
+            list.hit_test_in(&index, point, 0.0)
+        );
+
+        let viewport = Rect::new(0.0, 0.0, 19.0, 19.0);
+        let mut visible = DisplayList::new();
+        let stats = list.replay_culled_in(&index, viewport, &mut visible);
+        assert_eq!(visible.symbols.len(), 4);
+        assert_eq!(stats.skipped_symbols, 1996);
+        // Lists this large cull and hit test through their own cached index.
+        let mut culled = DisplayList::new();
+        let stats = list.replay_culled(viewport, &mut culled);
+        assert_eq!(culled, visible);
+        assert_eq!((stats.drawn, stats.skipped_symbols), (4, 1996));
+        let far = Affine::translate((1000.0, 1000.0));
+        list.push(symbol(
+            Rect::new(0.0, 0.0, 5.0, 5.0),
+            vec![fill_op(Color::BLACK)],
+            far,
+        ));
+        let far = Point::new(1002.0, 1002.0);
+        assert_eq!(
+            list.hit_test(far, 0.0),
+            vec![Hit {
+                symbol: 2000,
+                op: 0
+            }]
+        );
+        list.symbols[2000].transform = Affine::IDENTITY;
+        list.invalidate_index();
+        assert!(list.hit_test(far, 0.0).is_empty());
+        assert_eq!(list.hit_test(Point::new(2.0, 2.0), 0.0).len(), 2);
+        list.symbols.pop();
+
+        // Symbols with bounds which aren't finite are left out rather than panicking.
+        let mut nan = list.symbols[0].clone();
+        nan.transform = Affine::translate((f64::NAN, 0.0));
+        for i in 0..100 {
+            index.insert(2000 + i, &nan);
+        }
+        assert_eq!(index.len(), 2000);
+        // Huge symbols have an infinite area, so the growth of nodes can be NaN.
+        let mut huge = list.symbols[0].clone();
+        huge.transform = Affine::scale(1e300);
+        for i in 0..100 {
+            index.insert(2000 + i, &huge);
+        }
+        assert_eq!(index.len(), 2100);
+        assert_eq!(index.query_point(point).len(), 101);
+    }
+}
@@ -1,9 +1,16 @@
 #![cfg(feature = "svg")]
+use crate::compact::{path_data, round};
+use crate::metadata::{Metadata, ViewerLayer};
+use crate::offset::StrokeAlignment;
 use crate::whisperer::PaintOpRef;
-use kurbo::{Affine, Shape, Size, Stroke};
+use kurbo::{Affine, PathEl, Point, Rect, Shape, Size, Stroke};
 use peniko::BrushRef;
 use peniko::Color;
+use peniko::{BlendMode, Mix};
+use peniko::{Extend, Gradient, GradientKind};
+use std::borrow::Cow;
 use std::{fmt, io};
+use svg::node::element::Element;
 use svg::Node;
 
 //
@@ -46,14 +53,72 @@ struct State {
     clip: Option<Id>,
 }
 
+/// Options controlling the document written by [`Svg`].
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+    /// The area of the drawing shown in the document, defaults to the origin and its size.
+    pub view_box: Option<Rect>,
+    /// The units of the `width` and `height` of the document, e.g. `"mm"`, unitless if `None`.
+    pub units: Option<String>,
+    /// A color filling the view box behind everything else.
+    pub background: Option<Color>,
+    /// The number of decimal places numbers are rounded to, full precision if `None`.
+    pub precision: Option<usize>,
+    /// Tolerance used to convert shapes without an SVG element of their own into paths.
+    pub tolerance: f64,
+    /// Leaves out identity transforms and attributes with their default value.
+    pub omit_defaults: bool,
+    /// Writes path data without commas or spaces around commands, e.g. `M10 10L15 15Z`.
+    pub compact_paths: bool,
+    /// Viewer layers, which become Inkscape layers.
+    ///
+    /// Ops on these with [`Metadata::layer`] are put in groups Inkscape shows as layers,
+    /// hidden ones with `display:none`. Groups keep the order ops are painted in, so a
+    /// layer painted on in turns is split into several with the same label.
+    pub layers: Vec<ViewerLayer>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            view_box: None,
+            units: None,
+            background: None,
+            precision: None,
+            tolerance: 1e-3,
+            omit_defaults: false,
+            compact_paths: false,
+            layers: Vec::new(),
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Options for small output, rounding to `precision` decimal places.
+    pub fn minified(precision: usize) -> Self {
+        Self {
+            precision: Some(precision),
+            omit_defaults: true,
+            compact_paths: true,
+            ..Self::default()
+        }
+    }
+
+    fn num(&self, x: f64) -> f64 {
+        round(x, self.precision)
+    }
+}
+
 pub struct Svg {
     size: Size,
-    // I believe these will still be needed when `PushLayer` is implemented, but since I haven't
-    // needed it, I haven't implemented it yet.
-    _stack: Vec<State>,
+    options: SvgOptions,
+    // Groups which haven't been ended yet, shapes go in the innermost one.
+    layers: Vec<Element>,
+    // The number of groups begun for each layer op which hasn't been popped yet.
+    pushed: Vec<usize>,
     _state: State,
     doc: svg::Document,
-    _next_id: u64,
+    next_id: u64,
 }
 
 /// An SVG brush
@@ -78,40 +143,407 @@ struct Attrs<'a> {
 
 impl Svg {
     pub fn new(size: Size) -> Self {
+        Self::with_options(size, SvgOptions::default())
+    }
+
+    /// A document `size` wide and high, in `options.units`.
+    pub fn with_options(size: Size, options: SvgOptions) -> Self {
+        let mut doc = root(size, &options);
+        if let Some(rect) = background(size, &options) {
+            doc.append(rect);
+        }
         Svg {
             size,
-            doc: svg::Document::new(),
-            _next_id: 0,
+            options,
+            doc,
+            next_id: 0,
             _state: State::default(),
-            _stack: Vec::new(),
+            layers: Vec::new(),
+            pushed: Vec::new(),
         }
     }
 
+    pub fn options(&self) -> &SvgOptions {
+        &self.options
+    }
+
     pub fn size(&self) -> Size {
         self.size
     }
 
+    /// Writes the document, ending layers which haven't been popped like
+    /// [`SvgWriter::finish`].
     pub fn write(&self, writer: impl io::Write) -> io::Result<()> {
-        svg::write(writer, &self.doc)
+        svg::write(writer, &*self.closed())
     }
 
     /// Returns an object that can write the svg somewhere.
-    pub fn display(&self) -> &impl fmt::Display {
-        &self.doc
+    pub fn display(&self) -> impl fmt::Display + '_ {
+        self.closed()
+    }
+
+    /// The document with the layers which haven't been popped ended.
+    fn closed(&self) -> Cow<'_, svg::Document> {
+        if self.layers.is_empty() {
+            return Cow::Borrowed(&self.doc);
+        }
+        let mut layers = self.layers.iter().rev().cloned();
+        let mut inner = layers.next().unwrap();
+        for mut layer in layers {
+            layer.append(inner);
+            inner = layer;
+        }
+        Cow::Owned(self.doc.clone().add(inner))
+    }
+}
+
+/// The `svg` element of a document `size` wide and high, without children.
+fn root(size: Size, options: &SvgOptions) -> svg::Document {
+    let view_box = options.view_box.unwrap_or(size.to_rect());
+    let units = options.units.as_deref().unwrap_or_default();
+    let mut doc = svg::Document::new();
+    if !options.layers.is_empty() {
+        doc.assign(
+            "xmlns:inkscape",
+            "http://www.inkscape.org/namespaces/inkscape",
+        );
+    }
+    doc.set("width", format!("{}{units}", options.num(size.width)))
+        .set("height", format!("{}{units}", options.num(size.height)))
+        .set(
+            "viewBox",
+            [
+                view_box.x0,
+                view_box.y0,
+                view_box.width(),
+                view_box.height(),
+            ]
+            .map(|x| options.num(x).to_string())
+            .join(" "),
+        )
+}
+
+fn background(size: Size, options: &SvgOptions) -> Option<svg::node::element::Rectangle> {
+    let color = options.background?;
+    let view_box = options.view_box.unwrap_or(size.to_rect());
+    let mut rect = svg::node::element::Rectangle::new()
+        .set("x", options.num(view_box.x0))
+        .set("y", options.num(view_box.y0))
+        .set("width", options.num(view_box.width()))
+        .set("height", options.num(view_box.height()))
+        .set("fill", fmt_color(color));
+    if !(options.omit_defaults && color.a == u8::MAX) {
+        rect.assign("fill-opacity", fmt_opacity(color, options));
+    }
+    Some(rect)
+}
+
+/// Where the elements for paint ops go, either a document in memory or a stream.
+trait SvgSink {
+    fn options(&self) -> &SvgOptions;
+    fn clip(&self) -> Option<Id>;
+    fn next_id(&mut self) -> Id;
+    fn append(&mut self, node: Box<dyn Node>);
+    /// Starts a group which elements are appended to until it is ended.
+    fn begin_group(&mut self, group: Element);
+    fn end_group(&mut self);
+    /// The number of groups begun for each open layer.
+    fn pushed(&mut self) -> &mut Vec<usize>;
+}
+
+impl SvgSink for Svg {
+    fn options(&self) -> &SvgOptions {
+        &self.options
+    }
+
+    fn clip(&self) -> Option<Id> {
+        self._state.clip
+    }
+
+    fn next_id(&mut self) -> Id {
+        let x = Id(self.next_id);
+        self.next_id += 1;
+        x
+    }
+
+    fn append(&mut self, node: Box<dyn Node>) {
+        match self.layers.last_mut() {
+            Some(layer) => layer.append(node),
+            None => self.doc.append(node),
+        }
+    }
+
+    fn begin_group(&mut self, group: Element) {
+        self.layers.push(group);
+    }
+
+    fn end_group(&mut self) {
+        if let Some(group) = self.layers.pop() {
+            self.append(group.into());
+        }
+    }
+
+    fn pushed(&mut self) -> &mut Vec<usize> {
+        &mut self.pushed
+    }
+}
+
+/// Writes an SVG document to `W` as ops arrive, rather than building it in memory.
+///
+/// Clip paths are written inline right before the group they clip, so memory use doesn't
+/// grow with the drawing. The output is the same as [`Svg`] writes for the same ops.
+/// Every element is written separately, so an unbuffered `W` should be wrapped in a
+/// `BufWriter`.
+pub struct SvgWriter<W: io::Write> {
+    writer: W,
+    size: Size,
+    options: SvgOptions,
+    next_id: u64,
+    // The names of the groups which haven't been ended yet.
+    open: Vec<String>,
+    // The number of groups begun for each layer op which hasn't been popped yet.
+    pushed: Vec<usize>,
+    error: Option<io::Error>,
+}
+
+/// The start tag of `element`, which must not have children.
+fn start_tag(element: &impl fmt::Display) -> String {
+    let tag = element.to_string();
+    match tag.strip_suffix("/>") {
+        Some(tag) => format!("{tag}>"),
+        None => tag,
+    }
+}
+
+impl<W: io::Write> SvgWriter<W> {
+    pub fn new(writer: W, size: Size) -> Self {
+        Self::with_options(writer, size, SvgOptions::default())
+    }
+
+    /// Writes the start of a document `size` wide and high, in `options.units`.
+    pub fn with_options(writer: W, size: Size, options: SvgOptions) -> Self {
+        let mut this = Self {
+            writer,
+            size,
+            options,
+            next_id: 0,
+            open: Vec::new(),
+            pushed: Vec::new(),
+            error: None,
+        };
+        this.write(format_args!("{}", start_tag(&root(size, &this.options))));
+        if let Some(rect) = background(size, &this.options) {
+            this.write(format_args!("\n{rect}"));
+        }
+        this
+    }
+
+    pub fn options(&self) -> &SvgOptions {
+        &self.options
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// Ends layers which haven't been popped and the document, returning the writer
+    /// or the first error writing to it.
+    pub fn finish(mut self) -> io::Result<W> {
+        while !self.open.is_empty() {
+            self.end_group();
+        }
+        self.write(format_args!("\n</svg>"));
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, args: fmt::Arguments<'_>) {
+        if self.error.is_none() {
+            self.error = self.writer.write_fmt(args).err();
+        }
+    }
+}
+
+impl<W: io::Write> SvgSink for SvgWriter<W> {
+    fn options(&self) -> &SvgOptions {
+        &self.options
+    }
+
+    fn clip(&self) -> Option<Id> {
+        None
+    }
+
+    fn next_id(&mut self) -> Id {
+        let x = Id(self.next_id);
+        self.next_id += 1;
+        x
+    }
+
+    fn append(&mut self, node: Box<dyn Node>) {
+        self.write(format_args!("\n{node}"));
+    }
+
+    fn begin_group(&mut self, mut group: Element) {
+        let children = std::mem::take(group.get_children_mut());
+        self.write(format_args!("\n{}", start_tag(&group)));
+        for child in children {
+            self.append(child);
+        }
+        self.open.push(group.get_name().clone());
+    }
+
+    fn end_group(&mut self) {
+        if let Some(name) = self.open.pop() {
+            self.write(format_args!("\n</{name}>"));
+        }
+    }
+
+    fn pushed(&mut self) -> &mut Vec<usize> {
+        &mut self.pushed
+    }
+}
+
+/// Escapes text for use in element content and attribute values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
     }
+    out
+}
 
-    // FIXME we don't support clip yet.
-    /*
-        fn next_id(&mut self) -> Id {
-            let x = Id(self.next_id);
-            self.next_id += 1;
-            x
+/// A group carrying `metadata`, an `<a>` if it has a link and a `<g>` otherwise.
+fn metadata_group(metadata: &Metadata) -> Element {
+    let mut group = match &metadata.link {
+        Some(link) => {
+            let mut a = Element::new("a");
+            a.assign("href", escape(link));
+            a
         }
-    */
+        None => Element::new("g"),
+    };
+    if let Some(id) = &metadata.id {
+        group.assign("id", escape(id));
+    }
+    if !metadata.classes.is_empty() {
+        group.assign("class", escape(&metadata.classes.join(" ")));
+    }
+    for (key, value) in &metadata.data {
+        group.assign(format!("data-{key}"), escape(value));
+    }
+    // As text, since elements put their text on a line of its own which would end up in
+    // the tooltip.
+    if let Some(title) = &metadata.title {
+        group.append(svg::node::Text::new(format!(
+            "<title>{}</title>",
+            escape(title)
+        )));
+    }
+    if let Some(description) = &metadata.description {
+        group.append(svg::node::Text::new(format!(
+            "<desc>{}</desc>",
+            escape(description)
+        )));
+    }
+    group
+}
+
+/// An Inkscape layer for the viewer layer `metadata` is on, if it is in the options.
+fn viewer_layer_group(options: &SvgOptions, metadata: Option<&Metadata>) -> Option<Element> {
+    let name = metadata?.layer.as_deref()?;
+    let layer = options.layers.iter().find(|layer| layer.name == name)?;
+    let mut group = Element::new("g");
+    group.assign("inkscape:groupmode", "layer");
+    group.assign("inkscape:label", escape(&layer.name));
+    if !layer.visible {
+        group.assign("style", "display:none");
+    }
+    Some(group)
 }
 
-fn xf_val(xf: &Affine) -> svg::node::Value {
-    let xf = xf.as_coeffs();
+fn push_layer(
+    sink: &mut impl SvgSink,
+    metadata: Option<&Metadata>,
+    blend: BlendMode,
+    alpha: f32,
+    transform: Affine,
+    shape: &impl Shape,
+) {
+    let mut groups = 1;
+    if let Some(layer) = viewer_layer_group(sink.options(), metadata) {
+        sink.begin_group(layer);
+        groups += 1;
+    }
+    let id = append_clip_path(sink, transform, shape, None);
+    let mut group = metadata.map_or_else(|| Element::new("g"), metadata_group);
+    group.assign("clip-path", format!("url(#{})", id.to_string()));
+    if alpha != 1.0 {
+        group.assign("opacity", sink.options().num(alpha as f64));
+    }
+    if let Some(mode) = mix_blend_mode(blend.mix) {
+        group.assign("style", format!("mix-blend-mode:{mode}"));
+    }
+    sink.begin_group(group);
+    sink.pushed().push(groups);
+}
+
+/// Appends a clip path of `shape` with the clip rule `rule`, returning its id.
+fn append_clip_path(
+    sink: &mut impl SvgSink,
+    transform: Affine,
+    shape: &impl Shape,
+    rule: Option<&str>,
+) -> Id {
+    let id = sink.next_id();
+    let mut clip_path = svg::node::element::ClipPath::new().set("id", id);
+    if let Some(rule) = rule {
+        clip_path.assign("clip-rule", rule);
+    }
+    clip_path.append(shape_node(
+        shape,
+        &Attrs {
+            xf: transform,
+            ..Attrs::default()
+        },
+        sink.options(),
+    ));
+    sink.append(clip_path.into());
+    id
+}
+
+fn mix_blend_mode(mix: Mix) -> Option<&'static str> {
+    Some(match mix {
+        Mix::Normal | Mix::Clip => return None,
+        Mix::Multiply => "multiply",
+        Mix::Screen => "screen",
+        Mix::Overlay => "overlay",
+        Mix::Darken => "darken",
+        Mix::Lighten => "lighten",
+        Mix::ColorDodge => "color-dodge",
+        Mix::ColorBurn => "color-burn",
+        Mix::HardLight => "hard-light",
+        Mix::SoftLight => "soft-light",
+        Mix::Difference => "difference",
+        Mix::Exclusion => "exclusion",
+        Mix::Hue => "hue",
+        Mix::Saturation => "saturation",
+        Mix::Color => "color",
+        Mix::Luminosity => "luminosity",
+    })
+}
+
+fn xf_val(xf: &Affine, options: &SvgOptions) -> svg::node::Value {
+    let xf = xf.as_coeffs().map(|x| options.num(x));
     format!(
         "matrix({} {} {} {} {} {})",
         xf[0], xf[1], xf[2], xf[3], xf[4], xf[5]
@@ -122,14 +554,17 @@ fn xf_val(xf: &Affine) -> svg::node::Value {
 impl Attrs<'_> {
     // allow clippy warning for `width != 1.0` in if statement
     #[allow(clippy::float_cmp)]
-    fn apply_to(&self, node: &mut impl Node) {
-        node.assign("transform", xf_val(&self.xf));
+    fn apply_to(&self, node: &mut impl Node, options: &SvgOptions) {
+        let defaults = options.omit_defaults;
+        if !(defaults && self.xf == Affine::IDENTITY) {
+            node.assign("transform", xf_val(&self.xf, options));
+        }
         if let Some(id) = self.clip {
             node.assign("clip-path", format!("url(#{})", id.to_string()));
         }
         if let Some((ref brush, rule)) = self.fill {
             node.assign("fill", brush.color());
-            if let Some(opacity) = brush.opacity() {
+            if let Some(opacity) = brush.opacity(options) {
                 node.assign("fill-opacity", opacity);
             }
             if let Some(rule) = rule {
@@ -140,17 +575,19 @@ impl Attrs<'_> {
         }
         if let Some((ref stroke, style)) = self.stroke {
             node.assign("stroke", stroke.color());
-            if let Some(opacity) = stroke.opacity() {
+            if let Some(opacity) = stroke.opacity(options) {
                 node.assign("stroke-opacity", opacity);
             }
 
             if style.width != 1.0 {
-                node.assign("stroke-width", style.width);
+                node.assign("stroke-width", options.num(style.width));
             }
             use kurbo::Join;
             match style.join {
                 Join::Miter => {
-                    node.assign("stroke-miterlimit", style.miter_limit);
+                    if !(defaults && style.miter_limit == 4.0) {
+                        node.assign("stroke-miterlimit", options.num(style.miter_limit));
+                    }
                 }
                 Join::Round => {
                     node.assign("stroke-linejoin", "round");
@@ -171,10 +608,11 @@ impl Attrs<'_> {
             }
 
             if !style.dash_pattern.is_empty() {
-                node.assign("stroke-dasharray", style.dash_pattern.to_vec());
+                let dashes: Vec<f64> = style.dash_pattern.iter().map(|x| options.num(*x)).collect();
+                node.assign("stroke-dasharray", dashes);
             }
             if style.dash_offset != 0.0 {
-                node.assign("stroke-dashoffset", style.dash_offset);
+                node.assign("stroke-dashoffset", options.num(style.dash_offset));
             }
         }
     }
@@ -188,9 +626,11 @@ impl Brush {
         }
     }
 
-    fn opacity(&self) -> Option<svg::node::Value> {
+    /// The opacity, `None` if it is the default and `omit_defaults` is set.
+    fn opacity(&self, options: &SvgOptions) -> Option<svg::node::Value> {
         match self.kind {
-            BrushKind::Solid(color) => Some(fmt_opacity(color).into()),
+            BrushKind::Solid(color) if options.omit_defaults && color.a == u8::MAX => None,
+            BrushKind::Solid(color) => Some(fmt_opacity(color, options).into()),
             BrushKind::Ref(_) => None,
         }
     }
@@ -219,120 +659,558 @@ fn fmt_color(color: Color) -> String {
 }
 
 // Opacity as value from [0, 1]
-fn fmt_opacity(color: Color) -> String {
-    format!("{}", color.a / u8::MAX)
+fn fmt_opacity(color: Color, options: &SvgOptions) -> String {
+    format!("{}", options.num(color.a as f64 / u8::MAX as f64))
 }
 
-fn add_shape(node: &mut impl Node, shape: impl Shape, attrs: &Attrs) {
+fn shape_node(shape: &impl Shape, attrs: &Attrs, options: &SvgOptions) -> Box<dyn Node> {
+    let num = |x: f64| options.num(x);
     if let Some(circle) = shape.as_circle() {
         let mut x = svg::node::element::Circle::new()
-            .set("cx", circle.center.x)
-            .set("cy", circle.center.y)
-            .set("r", circle.radius);
-        attrs.apply_to(&mut x);
-        node.append(x);
+            .set("cx", num(circle.center.x))
+            .set("cy", num(circle.center.y))
+            .set("r", num(circle.radius));
+        attrs.apply_to(&mut x, options);
+        x.into()
     } else if let Some(round_rect) = shape
         .as_rounded_rect()
         .filter(|r| r.radii().as_single_radius().is_some())
     {
+        let radius = num(round_rect.radii().as_single_radius().unwrap());
         let mut x = svg::node::element::Rectangle::new()
-            .set("x", round_rect.origin().x)
-            .set("y", round_rect.origin().y)
-            .set("width", round_rect.width())
-            .set("height", round_rect.height())
-            .set("rx", round_rect.radii().as_single_radius().unwrap())
-            .set("ry", round_rect.radii().as_single_radius().unwrap());
-        attrs.apply_to(&mut x);
-        node.append(x);
+            .set("x", num(round_rect.origin().x))
+            .set("y", num(round_rect.origin().y))
+            .set("width", num(round_rect.width()))
+            .set("height", num(round_rect.height()))
+            .set("rx", radius)
+            .set("ry", radius);
+        attrs.apply_to(&mut x, options);
+        x.into()
     } else if let Some(rect) = shape.as_rect() {
         let mut x = svg::node::element::Rectangle::new()
-            .set("x", rect.origin().x)
-            .set("y", rect.origin().y)
-            .set("width", rect.width())
-            .set("height", rect.height());
-        attrs.apply_to(&mut x);
-        node.append(x);
+            .set("x", num(rect.origin().x))
+            .set("y", num(rect.origin().y))
+            .set("width", num(rect.width()))
+            .set("height", num(rect.height()));
+        attrs.apply_to(&mut x, options);
+        x.into()
     } else {
-        let mut path = svg::node::element::Path::new().set("d", shape.into_path(1e-3).to_svg());
-        attrs.apply_to(&mut path);
-        node.append(path);
-    }
-}
-
-impl SceneWhisperer for Svg {
-    fn apply_paint_op(
-        &mut self,
-        op: PaintOpRef<'_, '_>,
-        transform: Affine,
-        _brush_transform: Option<Affine>,
-        shape: &impl Shape,
-    ) {
-        match op {
-            PaintOpRef::Fill { brush, .. } => {
-                //let foo = brush.to_owned();
-                let fill_brush = match brush {
-                    BrushRef::Solid(color) => Some((
-                        Brush {
-                            kind: BrushKind::Solid(color),
-                        },
-                        None,
-                    )),
-                    // FIXME
-                    _ => None,
+        let path = shape.to_path(options.tolerance);
+        let data = if options.compact_paths {
+            path_data(path, options.precision)
+        } else if options.precision.is_some() {
+            let mut path = path;
+            for el in path.elements_mut() {
+                *el = match *el {
+                    PathEl::MoveTo(p) => PathEl::MoveTo(round_point(p, options)),
+                    PathEl::LineTo(p) => PathEl::LineTo(round_point(p, options)),
+                    PathEl::QuadTo(p1, p2) => {
+                        PathEl::QuadTo(round_point(p1, options), round_point(p2, options))
+                    }
+                    PathEl::CurveTo(p1, p2, p3) => PathEl::CurveTo(
+                        round_point(p1, options),
+                        round_point(p2, options),
+                        round_point(p3, options),
+                    ),
+                    PathEl::ClosePath => PathEl::ClosePath,
                 };
-                //let brush = brush.make_brush(self, || shape.bounding_box());
-                add_shape(
-                    &mut self.doc,
-                    shape,
-                    &Attrs {
-                        xf: transform,
-                        clip: self._state.clip,
-                        fill: fill_brush, // Some((brush, None)),
-                        ..Attrs::default()
-                    },
-                );
             }
+            path.to_svg()
+        } else {
+            path.to_svg()
+        };
+        let mut path = svg::node::element::Path::new().set("d", data);
+        attrs.apply_to(&mut path, options);
+        path.into()
+    }
+}
 
-            PaintOpRef::Stroke { style, brush, .. } => {
-                let stroke_brush = match brush {
-                    BrushRef::Solid(color) => Some((
-                        Brush {
-                            kind: BrushKind::Solid(color),
-                        },
-                        style,
-                    )),
-                    // FIXME
-                    _ => None,
-                };
-                //let brush = brush.make_brush(self, || shape.bounding_box());
-                add_shape(
-                    &mut self.doc,
+fn round_point(p: Point, options: &SvgOptions) -> Point {
+    Point::new(options.num(p.x), options.num(p.y))
+}
+
+/// The SVG brush for `brush`, appending the element it refers to to `sink` if any.
+fn brush_attr(
+    sink: &mut impl SvgSink,
+    brush: BrushRef<'_>,
+    brush_transform: Option<Affine>,
+) -> Option<Brush> {
+    match brush {
+        BrushRef::Solid(color) => Some(Brush {
+            kind: BrushKind::Solid(color),
+        }),
+        BrushRef::Gradient(gradient) => gradient_brush(sink, gradient, brush_transform),
+        // FIXME
+        BrushRef::Image(_) => None,
+    }
+}
+
+/// A gradient element in the user space of the shapes painted with it.
+///
+/// Colors are interpolated between the sRGB encoded stops, as vello does by default, which
+/// is also the default for SVG but is written out as `color-interpolation` so that viewers
+/// configured otherwise agree. The PDF backends don't paint gradients yet, so there is
+/// nothing to keep consistent there. Sweep gradients have no SVG element.
+fn gradient_brush(
+    sink: &mut impl SvgSink,
+    gradient: &Gradient,
+    brush_transform: Option<Affine>,
+) -> Option<Brush> {
+    if let GradientKind::Sweep { .. } = gradient.kind {
+        return None;
+    }
+    let id = sink.next_id();
+    let options = sink.options();
+    let num = |x: f64| options.num(x);
+    let mut element = match gradient.kind {
+        GradientKind::Linear { start, end } => {
+            let mut element = Element::new("linearGradient");
+            element.assign("x1", num(start.x));
+            element.assign("y1", num(start.y));
+            element.assign("x2", num(end.x));
+            element.assign("y2", num(end.y));
+            element
+        }
+        GradientKind::Radial {
+            start_center,
+            start_radius,
+            end_center,
+            end_radius,
+        } => {
+            let mut element = Element::new("radialGradient");
+            element.assign("cx", num(end_center.x));
+            element.assign("cy", num(end_center.y));
+            element.assign("r", num(end_radius as f64));
+            element.assign("fx", num(start_center.x));
+            element.assign("fy", num(start_center.y));
+            if start_radius != 0.0 {
+                element.assign("fr", num(start_radius as f64));
+            }
+            element
+        }
+        GradientKind::Sweep { .. } => unreachable!(),
+    };
+    element.assign("id", id);
+    element.assign("gradientUnits", "userSpaceOnUse");
+    element.assign("color-interpolation", "sRGB");
+    match gradient.extend {
+        Extend::Pad => (),
+        Extend::Repeat => element.assign("spreadMethod", "repeat"),
+        Extend::Reflect => element.assign("spreadMethod", "reflect"),
+    }
+    if let Some(xf) = brush_transform.filter(|xf| *xf != Affine::IDENTITY) {
+        element.assign("gradientTransform", xf_val(&xf, options));
+    }
+    for stop in gradient.stops.iter() {
+        let mut node = Element::new("stop");
+        // Offsets are single precision, widening them unrounded would add digits.
+        match options.precision {
+            Some(_) => node.assign("offset", options.num(stop.offset as f64)),
+            None => node.assign("offset", stop.offset),
+        }
+        node.assign("stop-color", fmt_color(stop.color));
+        if !(options.omit_defaults && stop.color.a == u8::MAX) {
+            node.assign("stop-opacity", fmt_opacity(stop.color, options));
+        }
+        element.append(node);
+    }
+    sink.append(element.into());
+    Some(Brush {
+        kind: BrushKind::Ref(id),
+    })
+}
+
+fn fill_attr(
+    sink: &mut impl SvgSink,
+    style: peniko::Fill,
+    brush: BrushRef<'_>,
+    brush_transform: Option<Affine>,
+) -> Option<(Brush, Option<&'static str>)> {
+    let rule = match style {
+        peniko::Fill::NonZero => None,
+        peniko::Fill::EvenOdd => Some("evenodd"),
+    };
+    Some((brush_attr(sink, brush, brush_transform)?, rule))
+}
+
+fn stroke_attr<'a>(
+    sink: &mut impl SvgSink,
+    style: &'a Stroke,
+    brush: BrushRef<'_>,
+    brush_transform: Option<Affine>,
+) -> Option<(Brush, &'a Stroke)> {
+    Some((brush_attr(sink, brush, brush_transform)?, style))
+}
+
+fn paint_op(
+    sink: &mut impl SvgSink,
+    metadata: Option<&Metadata>,
+    op: PaintOpRef<'_, '_>,
+    transform: Affine,
+    brush_transform: Option<Affine>,
+    shape: &impl Shape,
+) {
+    match op {
+        PaintOpRef::Fill { style, brush } => {
+            let fill = fill_attr(sink, style, brush, brush_transform);
+            let node = shape_node(
+                shape,
+                &Attrs {
+                    xf: transform,
+                    clip: sink.clip(),
+                    fill,
+                    ..Attrs::default()
+                },
+                sink.options(),
+            );
+            sink.append(node);
+        }
+
+        PaintOpRef::Stroke {
+            style,
+            brush,
+            alignment,
+        } => {
+            // Clip to the inside or outside of the shape and stroke twice as wide, a frame
+            // around the shape makes the even-odd rule clip to the outside.
+            let clip = match alignment {
+                StrokeAlignment::Center => None,
+                StrokeAlignment::Inside => Some(append_clip_path(sink, transform, shape, None)),
+                StrokeAlignment::Outside => {
+                    let tolerance = sink.options().tolerance;
+                    let grow = style.width * style.miter_limit.max(1.0);
+                    let mut outside = shape.bounding_box().inflate(grow, grow).to_path(tolerance);
+                    outside.extend(shape.path_elements(tolerance));
+                    Some(append_clip_path(sink, transform, &outside, Some("evenodd")))
+                }
+            };
+            let wide = Stroke {
+                width: style.width * 2.0,
+                ..style.clone()
+            };
+            let style = if clip.is_some() { &wide } else { style };
+            let attrs = Attrs {
+                xf: transform,
+                clip: sink.clip(),
+                stroke: stroke_attr(sink, style, brush, brush_transform),
+                ..Attrs::default()
+            };
+            let node = shape_node(shape, &attrs, sink.options());
+            if let Some(id) = clip {
+                let mut group = Element::new("g");
+                group.assign("clip-path", format!("url(#{})", id.to_string()));
+                sink.begin_group(group);
+                sink.append(node);
+                sink.end_group();
+            } else {
+                sink.append(node);
+            }
+        }
+        PaintOpRef::PushLayer { blend, alpha } => {
+            push_layer(sink, metadata, blend, alpha, transform, shape)
+        }
+        PaintOpRef::PopLayer => {
+            for _ in 0..sink.pushed().pop().unwrap_or(1) {
+                sink.end_group();
+            }
+        }
+    }
+}
+
+/// A fill followed by a centered stroke becomes a single element with both.
+///
+/// The elements are wrapped in a group carrying `metadata` and one for its viewer layer,
+/// unless there are layer ops, in which case the groups of the layers carry it.
+fn paint_ops<'a, 'b>(
+    sink: &mut impl SvgSink,
+    metadata: Option<&Metadata>,
+    ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    transform: Affine,
+    brush_transform: Option<Affine>,
+    shape: &impl Shape,
+) {
+    let ops: Vec<_> = ops.into_iter().collect();
+    let layers = ops
+        .iter()
+        .any(|op| matches!(op, PaintOpRef::PushLayer { .. } | PaintOpRef::PopLayer));
+    let wrap = metadata.filter(|_| !layers);
+    let viewer_layer = viewer_layer_group(sink.options(), wrap);
+    let in_viewer_layer = viewer_layer.is_some();
+    if let Some(group) = viewer_layer {
+        sink.begin_group(group);
+    }
+    // Leaves out the group if it would carry nothing, print colors are in the brushes and
+    // SVG has no outline for bookmarks.
+    let wrap = wrap.filter(|metadata| {
+        let rest = Metadata {
+            bookmark: None,
+            layer: None,
+            fill_color: None,
+            stroke_color: None,
+            overprint: false,
+            ..(*metadata).clone()
+        };
+        rest != Metadata::default()
+    });
+    if let Some(metadata) = wrap {
+        sink.begin_group(metadata_group(metadata));
+    }
+    let mut ops = ops.into_iter().peekable();
+    while let Some(op) = ops.next() {
+        if let PaintOpRef::Fill {
+            style: fill,
+            brush: fill_brush,
+        } = op
+        {
+            if let Some(PaintOpRef::Stroke {
+                style,
+                brush,
+                alignment: StrokeAlignment::Center,
+            }) = ops.peek().copied()
+            {
+                ops.next();
+                let fill = fill_attr(sink, fill, fill_brush, brush_transform);
+                let stroke = stroke_attr(sink, style, brush, brush_transform);
+                let node = shape_node(
                     shape,
                     &Attrs {
                         xf: transform,
-                        clip: self._state.clip,
-                        stroke: stroke_brush,
-                        ..Attrs::default()
+                        clip: sink.clip(),
+                        fill,
+                        stroke,
                     },
+                    sink.options(),
                 );
+                sink.append(node);
+                continue;
             }
-            PaintOpRef::PushLayer { .. } => {}
         }
+        paint_op(sink, metadata, op, transform, brush_transform, shape)
     }
+    if wrap.is_some() {
+        sink.end_group();
+    }
+    if in_viewer_layer {
+        sink.end_group();
+    }
+}
 
-    fn apply_paint_ops<'a, 'b, I>(
-        &mut self,
-        ops: I,
-        transform: Affine,
-        brush_transform: Option<Affine>,
-        shape: &impl Shape,
-    ) where
-        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
-    {
-        // FIXME this should be able to produce nicer output than just producing an element for
-        // each shape_op.
-        for op in ops {
-            self.apply_paint_op(op, transform, brush_transform, shape)
+/// Implements `SceneWhisperer` for an `SvgSink`.
+macro_rules! svg_whisperer {
+    ($ty:ty $(, $param:ident: $bound:path)?) => {
+        impl$(<$param: $bound>)? SceneWhisperer for $ty {
+            fn apply_paint_op(
+                &mut self,
+                op: PaintOpRef<'_, '_>,
+                transform: Affine,
+                brush_transform: Option<Affine>,
+                shape: &impl Shape,
+            ) {
+                paint_ops(self, None, [op], transform, brush_transform, shape);
+            }
+
+            fn apply_paint_ops<'a, 'b, I>(
+                &mut self,
+                ops: I,
+                transform: Affine,
+                brush_transform: Option<Affine>,
+                shape: &impl Shape,
+            ) where
+                I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+            {
+                paint_ops(self, None, ops, transform, brush_transform, shape);
+            }
+
+            fn apply_paint_ops_with_metadata<'a, 'b, I>(
+                &mut self,
+                metadata: &Metadata,
+                ops: I,
+                transform: Affine,
+                brush_transform: Option<Affine>,
+                shape: &impl Shape,
+            ) where
+                I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+            {
+                paint_ops(self, Some(metadata), ops, transform, brush_transform, shape);
+            }
+        }
+    };
+}
+
+svg_whisperer!(Svg);
+svg_whisperer!(SvgWriter<W>, W: io::Write);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_contains, fill, push, stroke};
+    use crate::{DynSceneWhisperer, SceneWhisperer};
+    use kurbo::{BezPath, Circle};
+    use peniko::{ColorStop, Fill, Gradient};
+
+    #[test]
+    fn svg_closes_open_layers() {
+        let size = Size::new(20.0, 20.0);
+        let mut svg = Svg::new(size);
+        let mut writer = SvgWriter::new(Vec::new(), size);
+        let ops = [push(0.5), push(0.5), fill(Color::BLACK)];
+        let circle = Circle::new((10.0, 10.0), 5.0);
+        for whisperer in [&mut svg as &mut dyn DynSceneWhisperer, &mut writer] {
+            whisperer.apply_paint_ops_dyn(&ops, Affine::IDENTITY, None, &circle.into());
+        }
+        let streamed = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let mut written = Vec::new();
+        svg.write(&mut written).unwrap();
+        assert_eq!(streamed, String::from_utf8(written).unwrap());
+        assert_eq!(streamed, svg.display().to_string());
+        assert!(streamed.contains("<circle"), "{streamed}");
+        assert_eq!(streamed.matches("</g>").count(), 2);
+    }
+
+    #[test]
+    fn fill_and_stroke_merge() {
+        let path = BezPath::from_svg("M0 0L10 0L10 10Z").unwrap();
+        let style = Stroke::new(1.0);
+        let stroke = |alignment| stroke(&style, Color::RED, alignment);
+        let paths = |svg: &Svg| svg.display().to_string().matches("<path").count();
+
+        let mut svg = Svg::new(Size::new(20.0, 20.0));
+        let ops = [fill(Color::BLACK), stroke(StrokeAlignment::Center)];
+        svg.apply_paint_ops(ops, Affine::IDENTITY, None, &path);
+        let out = svg.display().to_string();
+        assert_eq!(paths(&svg), 1);
+        let element = &out[out.find("<path").unwrap()..];
+        let element = &element[..element.find("/>").unwrap()];
+        assert!(
+            element.contains("fill=") && element.contains("stroke="),
+            "{element}"
+        );
+        // Even-odd fills keep their rule on the merged element.
+        let mut svg = Svg::new(Size::new(20.0, 20.0));
+        let even_odd = PaintOpRef::Fill {
+            style: Fill::EvenOdd,
+            brush: (&Color::BLACK).into(),
+        };
+        let ops = [even_odd, stroke(StrokeAlignment::Center)];
+        svg.apply_paint_ops(ops, Affine::IDENTITY, None, &path);
+        assert_eq!(paths(&svg), 1);
+        assert!(svg.display().to_string().contains("fill-rule=\"evenodd\""));
+
+        // Inside and outside strokes paint a different outline than the fill.
+        for alignment in [StrokeAlignment::Inside, StrokeAlignment::Outside] {
+            let mut svg = Svg::new(Size::new(20.0, 20.0));
+            let ops = [fill(Color::BLACK), stroke(alignment)];
+            svg.apply_paint_ops(ops, Affine::IDENTITY, None, &path);
+            assert!(paths(&svg) >= 2, "{alignment:?}");
+        }
+
+        // Ops in separate calls aren't merged, since their transforms may differ.
+        let mut svg = Svg::new(Size::new(20.0, 20.0));
+        svg.apply_paint_op(fill(Color::BLACK), Affine::IDENTITY, None, &path);
+        let moved = Affine::translate((5.0, 0.0));
+        svg.apply_paint_op(stroke(StrokeAlignment::Center), moved, None, &path);
+        assert_eq!(paths(&svg), 2);
+    }
+
+    #[test]
+    fn svg_aligned_strokes() {
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let style = Stroke::new(2.0);
+        let mut svg = Svg::with_options(Size::new(20.0, 20.0), SvgOptions::minified(2));
+        let inside = stroke(&style, Color::RED, StrokeAlignment::Inside);
+        svg.apply_paint_op(inside, Affine::IDENTITY, None, &square);
+        let outside = stroke(&style, Color::RED, StrokeAlignment::Outside);
+        svg.apply_paint_op(outside, Affine::IDENTITY, None, &square);
+        let out = svg.display().to_string();
+        assert_contains(
+            &out,
+            &[
+                "<clipPath id=\"a\">",
+                "<g clip-path=\"url(#a)\">",
+                "<clipPath clip-rule=\"evenodd\" id=\"b\">",
+                "<g clip-path=\"url(#b)\">",
+            ],
+        );
+        // Both strokes are twice as wide and follow the square itself.
+        assert_eq!(out.matches("stroke-width=\"4\"").count(), 2);
+        assert_eq!(out.matches("<rect").count(), 3);
+    }
+
+    #[test]
+    fn svg_fractional_opacity() {
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let style = Stroke::new(1.0);
+        let ops = [
+            push(0.25),
+            fill(Color::rgba8(0, 0, 0, 128)),
+            stroke(&style, Color::rgba8(255, 0, 0, 64), StrokeAlignment::Inside),
+            PaintOpRef::PopLayer,
+        ];
+        let stops = [
+            ColorStop::from((0.1234, Color::BLACK)),
+            ColorStop::from((1.0, Color::rgba8(255, 255, 255, 128))),
+        ];
+        let gradient = Gradient::new_linear((0.0, 0.0), (10.0, 0.0)).with_stops(stops);
+        let gradient = PaintOpRef::Fill {
+            style: Fill::NonZero,
+            brush: (&gradient).into(),
+        };
+        let draw = |options| {
+            let options = SvgOptions {
+                background: Some(Color::rgba8(255, 255, 255, 51)),
+                ..options
+            };
+            let mut svg = Svg::with_options(Size::new(10.0, 10.0), options);
+            svg.apply_paint_ops(ops, Affine::IDENTITY, None, &square);
+            svg.apply_paint_op(gradient, Affine::IDENTITY, None, &square);
+            let out = svg.display().to_string();
+            out
+        };
+        assert_contains(
+            &draw(SvgOptions::default()),
+            &[
+                "opacity=\"0.25\"",
+                "fill-opacity=\"0.5019607843137255\"",
+                "stroke-opacity=\"0.25098039215686274\"",
+                "fill-opacity=\"0.2\"",
+                "offset=\"0.1234\"",
+                "stop-opacity=\"0.5019607843137255\"",
+            ],
+        );
+        assert_contains(
+            &draw(SvgOptions::minified(2)),
+            &[
+                "opacity=\"0.25\"",
+                "fill-opacity=\"0.5\"",
+                "stroke-opacity=\"0.25\"",
+                "fill-opacity=\"0.2\"",
+                "offset=\"0.12\"",
+                "stop-opacity=\"0.5\"",
+            ],
+        );
+    }
+
+    #[test]
+    fn svg_writer_streams() {
+        let size = Size::new(20.0, 20.0);
+        let mut svg = Svg::new(size);
+        let mut writer = SvgWriter::new(Vec::new(), size);
+        let style = Stroke::new(2.0);
+        let ops = [
+            push(0.5),
+            fill(Color::BLACK),
+            stroke(&style, Color::RED, StrokeAlignment::Center),
+        ];
+        let circle = Circle::new((10.0, 10.0), 5.0);
+        let rect = Rect::new(0.0, 0.0, 5.0, 5.0);
+        for whisperer in [&mut svg as &mut dyn DynSceneWhisperer, &mut writer] {
+            whisperer.apply_paint_ops_dyn(&ops, Affine::IDENTITY, None, &circle.into());
+            whisperer.apply_paint_ops_dyn(&ops[1..], Affine::IDENTITY, None, &rect.into());
+            let pop = [PaintOpRef::PopLayer];
+            whisperer.apply_paint_ops_dyn(&pop, Affine::IDENTITY, None, &rect.into());
         }
+        let streamed = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(streamed, svg.display().to_string());
+        assert!(streamed.contains("<clipPath id=\"a\">"));
     }
 }
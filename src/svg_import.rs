@@ -0,0 +1,704 @@
+#![cfg(feature = "svg-import")]
+use crate::display_list::{DisplayList, Symbol};
+use crate::metadata::Metadata;
+use crate::offset::StrokeAlignment;
+use crate::shape::StaticShape;
+use crate::whisperer::PaintOp;
+use kurbo::{Affine, BezPath, Cap, Join, PathEl, Rect, Shape, Size, Stroke};
+use peniko::{BlendMode, Brush, Color, ColorStop, Extend, Fill, Gradient, Mix};
+use usvg::tiny_skia_path::PathSegment;
+
+/// Features of an SVG document which could not be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unsupported {
+    /// Filters are dropped and the content drawn unfiltered.
+    Filter,
+    /// Masks are dropped and the content drawn unmasked.
+    Mask,
+    /// Clip paths which are themselves clipped are only clipped by the outermost one.
+    NestedClipPath,
+    /// Paints using patterns are dropped.
+    Pattern,
+    /// Images are dropped.
+    Image,
+    /// Text is dropped since there are no fonts to convert it to paths with.
+    Text,
+}
+
+/// Something that was lost importing an element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The id of the element, empty if it didn't have one.
+    pub id: String,
+    pub unsupported: Unsupported,
+}
+
+/// The result of importing an SVG document.
+#[derive(Debug, Clone)]
+pub struct ImportedSvg {
+    pub size: Size,
+    pub display_list: DisplayList,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Converts an SVG document into a display list.
+///
+/// Paths and basic shapes, solid and gradient fills and strokes, transforms, group opacity,
+/// blend modes and clip paths are converted, anything else is reported in the diagnostics.
+pub fn import_svg(text: &str) -> Result<ImportedSvg, usvg::Error> {
+    let tree = usvg::Tree::from_str(text, &usvg::Options::default())?;
+    let size = Size::new(tree.size().width() as f64, tree.size().height() as f64);
+    let mut import = Importer {
+        svg: ImportedSvg {
+            size,
+            display_list: DisplayList::new(),
+            diagnostics: Vec::new(),
+        },
+        focal_radii: Vec::new(),
+    };
+    // usvg drops text it can't lay out and the focal radius of radial gradients, so look for
+    // them in the source.
+    if let Ok(doc) = usvg::roxmltree::Document::parse(text) {
+        for node in doc.descendants().filter(|n| n.has_tag_name("text")) {
+            import.diagnose(node.attribute("id").unwrap_or_default(), Unsupported::Text);
+        }
+        import.focal_radii = focal_radii(&doc, size);
+    }
+    import.group(tree.root());
+    Ok(import.svg)
+}
+
+struct Importer {
+    svg: ImportedSvg,
+    focal_radii: Vec<FocalRadius>,
+}
+
+/// A radial gradient in the source with a nonzero `fr`, along with the circles usvg keeps,
+/// which find it again when usvg renamed the gradient to resolve its units.
+struct FocalRadius {
+    id: String,
+    circles: [f32; 5],
+    fr: f32,
+}
+
+fn focal_radii(doc: &usvg::roxmltree::Document, size: Size) -> Vec<FocalRadius> {
+    use usvg::roxmltree::Node;
+    let gradients: Vec<Node> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("linearGradient") || n.has_tag_name("radialGradient"))
+        .collect();
+    // Attributes which aren't set are inherited from the gradient referenced by `href`.
+    let attribute = |node: Node, name: &str| {
+        let mut node = Some(node);
+        for _ in 0..16 {
+            let current = node?;
+            if let Some(value) = current.attribute(name) {
+                return Some(value.trim().to_string());
+            }
+            let href = current
+                .attribute(("http://www.w3.org/1999/xlink", "href"))
+                .or_else(|| current.attribute("href"))?;
+            let id = href.trim().strip_prefix('#')?;
+            node = gradients
+                .iter()
+                .copied()
+                .find(|n| n.attribute("id") == Some(id));
+        }
+        None
+    };
+    let view_box = doc
+        .root_element()
+        .attribute("viewBox")
+        .map(|v| {
+            v.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|s| !s.is_empty())
+                .filter_map(|s| s.parse::<f64>().ok())
+                .collect::<Vec<_>>()
+        })
+        .filter(|v| v.len() == 4)
+        .map_or(size, |v| Size::new(v[2], v[3]));
+    let diagonal = (view_box.width.powi(2) + view_box.height.powi(2)).sqrt() / 2f64.sqrt();
+    let mut out = Vec::new();
+    for &node in gradients
+        .iter()
+        .filter(|n| n.has_tag_name("radialGradient"))
+    {
+        let user_space = attribute(node, "gradientUnits").as_deref() == Some("userSpaceOnUse");
+        // Percentages are of the bounding box, or of the view box in user space.
+        let length = |name: &str, default: f64, of: f64| {
+            let Some(value) = attribute(node, name) else {
+                return Some(default);
+            };
+            match value.strip_suffix('%') {
+                Some(percent) => {
+                    let fraction = percent.trim().parse::<f64>().ok()? / 100.0;
+                    Some(if user_space { fraction * of } else { fraction })
+                }
+                None => value.trim_end_matches("px").trim().parse().ok(),
+            }
+        };
+        let half = |of: f64| if user_space { of / 2.0 } else { 0.5 };
+        let Some(fr) = length("fr", 0.0, diagonal).filter(|&fr| fr > 0.0) else {
+            continue;
+        };
+        let (Some(cx), Some(cy), Some(r)) = (
+            length("cx", half(view_box.width), view_box.width),
+            length("cy", half(view_box.height), view_box.height),
+            length("r", half(diagonal), diagonal),
+        ) else {
+            continue;
+        };
+        let (Some(fx), Some(fy)) = (
+            length("fx", cx, view_box.width),
+            length("fy", cy, view_box.height),
+        ) else {
+            continue;
+        };
+        out.push(FocalRadius {
+            id: node.attribute("id").unwrap_or_default().to_string(),
+            circles: [cx, cy, r, fx, fy].map(|x| x as f32),
+            fr: fr as f32,
+        });
+    }
+    out
+}
+
+fn affine(t: usvg::Transform) -> Affine {
+    Affine::new([
+        t.sx as f64,
+        t.ky as f64,
+        t.kx as f64,
+        t.sy as f64,
+        t.tx as f64,
+        t.ty as f64,
+    ])
+}
+
+fn bez_path(path: &usvg::tiny_skia_path::Path) -> BezPath {
+    let pt = |p: usvg::tiny_skia_path::Point| kurbo::Point::new(p.x as f64, p.y as f64);
+    let mut out = BezPath::new();
+    for seg in path.segments() {
+        match seg {
+            PathSegment::MoveTo(p) => out.move_to(pt(p)),
+            PathSegment::LineTo(p) => out.line_to(pt(p)),
+            PathSegment::QuadTo(p1, p2) => out.quad_to(pt(p1), pt(p2)),
+            PathSegment::CubicTo(p1, p2, p3) => out.curve_to(pt(p1), pt(p2), pt(p3)),
+            PathSegment::Close => out.close_path(),
+        }
+    }
+    out
+}
+
+fn mix(mode: usvg::BlendMode) -> Mix {
+    use usvg::BlendMode as B;
+    match mode {
+        B::Normal => Mix::Normal,
+        B::Multiply => Mix::Multiply,
+        B::Screen => Mix::Screen,
+        B::Overlay => Mix::Overlay,
+        B::Darken => Mix::Darken,
+        B::Lighten => Mix::Lighten,
+        B::ColorDodge => Mix::ColorDodge,
+        B::ColorBurn => Mix::ColorBurn,
+        B::HardLight => Mix::HardLight,
+        B::SoftLight => Mix::SoftLight,
+        B::Difference => Mix::Difference,
+        B::Exclusion => Mix::Exclusion,
+        B::Hue => Mix::Hue,
+        B::Saturation => Mix::Saturation,
+        B::Color => Mix::Color,
+        B::Luminosity => Mix::Luminosity,
+    }
+}
+
+fn color(c: usvg::Color, opacity: f32) -> Color {
+    Color::rgba8(c.red, c.green, c.blue, (opacity * 255.0).round() as u8)
+}
+
+fn stops(base: &usvg::BaseGradient, opacity: f32) -> Vec<ColorStop> {
+    base.stops()
+        .iter()
+        .map(|stop| ColorStop {
+            offset: stop.offset().get(),
+            color: color(stop.color(), stop.opacity().get() * opacity),
+        })
+        .collect()
+}
+
+fn extend(spread: usvg::SpreadMethod) -> Extend {
+    match spread {
+        usvg::SpreadMethod::Pad => Extend::Pad,
+        usvg::SpreadMethod::Reflect => Extend::Reflect,
+        usvg::SpreadMethod::Repeat => Extend::Repeat,
+    }
+}
+
+impl Importer {
+    fn diagnose(&mut self, id: &str, unsupported: Unsupported) {
+        self.svg.diagnostics.push(Diagnostic {
+            id: id.to_string(),
+            unsupported,
+        });
+    }
+
+    /// The `fr` of a radial gradient, found by id or else by its circles.
+    fn focal_radius(&self, g: &usvg::RadialGradient) -> f32 {
+        let circles = [g.cx(), g.cy(), g.r().get(), g.fx(), g.fy()];
+        let same = |a: &[f32; 5]| a.iter().zip(&circles).all(|(a, b)| (a - b).abs() < 1e-4);
+        self.focal_radii
+            .iter()
+            .find(|f| f.id == g.id())
+            .or_else(|| self.focal_radii.iter().find(|f| same(&f.circles)))
+            .map_or(0.0, |f| f.fr)
+    }
+
+    /// The brush and brush transform for a paint.
+    fn brush(&mut self, id: &str, paint: &usvg::Paint, opacity: f32) -> Option<(Brush, Affine)> {
+        match paint {
+            usvg::Paint::Color(c) => Some((Brush::Solid(color(*c, opacity)), Affine::IDENTITY)),
+            usvg::Paint::LinearGradient(g) => Some((
+                Brush::Gradient(
+                    Gradient::new_linear(
+                        (g.x1() as f64, g.y1() as f64),
+                        (g.x2() as f64, g.y2() as f64),
+                    )
+                    .with_extend(extend(g.spread_method()))
+                    .with_stops(stops(g, opacity).as_slice()),
+                ),
+                affine(g.transform()),
+            )),
+            usvg::Paint::RadialGradient(g) => Some((
+                Brush::Gradient(
+                    Gradient::new_two_point_radial(
+                        (g.fx() as f64, g.fy() as f64),
+                        self.focal_radius(g),
+                        (g.cx() as f64, g.cy() as f64),
+                        g.r().get(),
+                    )
+                    .with_extend(extend(g.spread_method()))
+                    .with_stops(stops(g, opacity).as_slice()),
+                ),
+                affine(g.transform()),
+            )),
+            usvg::Paint::Pattern(_) => {
+                self.diagnose(id, Unsupported::Pattern);
+                None
+            }
+        }
+    }
+
+    fn fill(&mut self, id: &str, fill: &usvg::Fill) -> Option<(PaintOp, Affine)> {
+        let (brush, brush_transform) = self.brush(id, fill.paint(), fill.opacity().get())?;
+        let style = match fill.rule() {
+            usvg::FillRule::NonZero => Fill::NonZero,
+            usvg::FillRule::EvenOdd => Fill::EvenOdd,
+        };
+        Some((PaintOp::Fill { style, brush }, brush_transform))
+    }
+
+    fn stroke(&mut self, id: &str, stroke: &usvg::Stroke) -> Option<(PaintOp, Affine)> {
+        let (brush, brush_transform) = self.brush(id, stroke.paint(), stroke.opacity().get())?;
+        let mut style = Stroke::new(stroke.width().get() as f64)
+            .with_miter_limit(stroke.miterlimit().get() as f64)
+            .with_join(match stroke.linejoin() {
+                usvg::LineJoin::Miter | usvg::LineJoin::MiterClip => Join::Miter,
+                usvg::LineJoin::Round => Join::Round,
+                usvg::LineJoin::Bevel => Join::Bevel,
+            })
+            .with_caps(match stroke.linecap() {
+                usvg::LineCap::Butt => Cap::Butt,
+                usvg::LineCap::Round => Cap::Round,
+                usvg::LineCap::Square => Cap::Square,
+            });
+        if let Some(dashes) = stroke.dasharray() {
+            style = style.with_dashes(stroke.dashoffset() as f64, dashes.iter().map(|&d| d as f64));
+        }
+        Some((
+            PaintOp::Stroke {
+                style,
+                brush,
+                alignment: StrokeAlignment::Center,
+            },
+            brush_transform,
+        ))
+    }
+
+    fn path(&mut self, path: &usvg::Path) {
+        if !path.is_visible() {
+            return;
+        }
+        let id = path.id();
+        let fill = path.fill().and_then(|fill| self.fill(id, fill));
+        let stroke = path.stroke().and_then(|stroke| self.stroke(id, stroke));
+        let ops = match path.paint_order() {
+            usvg::PaintOrder::FillAndStroke => [fill, stroke],
+            usvg::PaintOrder::StrokeAndFill => [stroke, fill],
+        };
+        let shape = StaticShape::BezPath(bez_path(path.data()));
+        let transform = affine(path.abs_transform());
+        // Fill and stroke share a symbol when their brush transforms agree.
+        let mut current: Option<Symbol> = None;
+        for (op, brush_transform) in ops.into_iter().flatten() {
+            let brush_transform = (brush_transform != Affine::IDENTITY).then_some(brush_transform);
+            match &mut current {
+                Some(symbol) if symbol.brush_transform == brush_transform => {
+                    symbol.paint_ops.push(op)
+                }
+                _ => {
+                    // Only the first symbol gets the id, which has to stay unique.
+                    let first = current.is_none();
+                    if let Some(symbol) = current.take() {
+                        self.svg.display_list.push(symbol);
+                    }
+                    current = Some(Symbol {
+                        shape: shape.clone(),
+                        paint_ops: vec![op],
+                        transform,
+                        brush_transform,
+                        metadata: id_metadata(id).filter(|_| first),
+                    });
+                }
+            }
+        }
+        if let Some(symbol) = current {
+            self.svg.display_list.push(symbol);
+        }
+    }
+
+    /// The combined outline of a clip path, in the coordinates of the group it clips.
+    fn clip_shape(&mut self, clip: &usvg::ClipPath) -> BezPath {
+        if clip.clip_path().is_some() {
+            self.diagnose(clip.id(), Unsupported::NestedClipPath);
+        }
+        let mut out = BezPath::new();
+        let transform = affine(clip.transform());
+        // Layers clip with the nonzero rule, so each path is rewound to cover its area with
+        // a positive winding, and their union is then just all of them together.
+        clip_paths(clip.root(), &mut |path| {
+            let rule = path
+                .fill()
+                .map_or(usvg::FillRule::NonZero, |fill| fill.rule());
+            let outline = transform * affine(path.abs_transform()) * bez_path(path.data());
+            out.extend(positive_winding(&outline, rule).iter())
+        });
+        out
+    }
+
+    fn group(&mut self, group: &usvg::Group) {
+        if !group.filters().is_empty() {
+            self.diagnose(group.id(), Unsupported::Filter);
+        }
+        if group.mask().is_some() {
+            self.diagnose(group.id(), Unsupported::Mask);
+        }
+        let blend = mix(group.blend_mode());
+        let alpha = group.opacity().get();
+        let clip = group.clip_path().map(|clip| self.clip_shape(clip));
+        let layer = clip.is_some() || alpha != 1.0 || blend != Mix::Normal;
+        if layer {
+            let shape = match clip {
+                Some(path) => StaticShape::BezPath(path),
+                None => {
+                    let r = group.layer_bounding_box();
+                    StaticShape::Rect(Rect::new(
+                        r.left() as f64,
+                        r.top() as f64,
+                        r.right() as f64,
+                        r.bottom() as f64,
+                    ))
+                }
+            };
+            self.svg.display_list.push(Symbol {
+                shape,
+                paint_ops: vec![PaintOp::PushLayer {
+                    blend: BlendMode::from(blend),
+                    alpha,
+                }],
+                transform: affine(group.abs_transform()),
+                brush_transform: None,
+                metadata: id_metadata(group.id()),
+            });
+        }
+        for node in group.children() {
+            match node {
+                usvg::Node::Group(group) => self.group(group),
+                usvg::Node::Path(path) => self.path(path),
+                usvg::Node::Image(image) => self.diagnose(image.id(), Unsupported::Image),
+                usvg::Node::Text(text) => self.diagnose(text.id(), Unsupported::Text),
+            }
+        }
+        if layer {
+            self.svg.display_list.push(Symbol {
+                shape: StaticShape::Rect(Rect::ZERO),
+                paint_ops: vec![PaintOp::PopLayer],
+                transform: Affine::IDENTITY,
+                brush_transform: None,
+                metadata: None,
+            });
+        }
+    }
+}
+
+/// Metadata with the id of an element, if it has one.
+fn id_metadata(id: &str) -> Option<Metadata> {
+    (!id.is_empty()).then(|| Metadata::new().with_id(id))
+}
+
+/// Rewinds the contours of a path so that it covers the same area as when filled with `rule`
+/// with a winding number which is never negative.
+///
+/// Contours are assumed not to cross each other. With the even-odd rule, those nested an even
+/// number of times get a positive area and the others a negative one. With the nonzero rule,
+/// those inside an outermost contour with a negative area are reversed along with it.
+fn positive_winding(path: &BezPath, rule: usvg::FillRule) -> BezPath {
+    let mut contours: Vec<BezPath> = Vec::new();
+    for el in path.elements() {
+        match (el, contours.last_mut()) {
+            (PathEl::MoveTo(_), _) | (_, None) => contours.push(BezPath::from_vec(vec![*el])),
+            (_, Some(contour)) => contour.push(*el),
+        }
+    }
+    // Filling closes contours, which their area and winding need to see.
+    for contour in &mut contours {
+        if contour.elements().last() != Some(&PathEl::ClosePath) {
+            contour.close_path();
+        }
+    }
+    // Where a contour starts is inside the contours enclosing it.
+    let start = |c: &BezPath| match c.elements().first() {
+        Some(PathEl::MoveTo(p)) => *p,
+        _ => kurbo::Point::ZERO,
+    };
+    let enclosing: Vec<Vec<usize>> = (0..contours.len())
+        .map(|i| {
+            let p = start(&contours[i]);
+            (0..contours.len())
+                .filter(|&j| j != i && contours[j].winding(p) != 0)
+                .collect()
+        })
+        .collect();
+    let mut out = BezPath::new();
+    for (i, contour) in contours.iter().enumerate() {
+        let reverse = match rule {
+            usvg::FillRule::EvenOdd => {
+                (contour.area() < 0.0) == enclosing[i].len().is_multiple_of(2)
+            }
+            usvg::FillRule::NonZero => {
+                let outermost = enclosing[i]
+                    .iter()
+                    .copied()
+                    .find(|&j| enclosing[j].is_empty())
+                    .unwrap_or(i);
+                contours[outermost].area() < 0.0
+            }
+        };
+        match reverse {
+            true => out.extend(contour.reverse_subpaths().iter()),
+            false => out.extend(contour.iter()),
+        }
+    }
+    out
+}
+
+fn clip_paths(group: &usvg::Group, f: &mut impl FnMut(&usvg::Path)) {
+    for node in group.children() {
+        match node {
+            usvg::Node::Group(group) => clip_paths(group, f),
+            usvg::Node::Path(path) => f(path),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use peniko::GradientKind;
+
+    #[test]
+    fn svg_import() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <defs>
+                <linearGradient id="lin" x1="0" y1="0" x2="10" y2="0"
+                        gradientUnits="userSpaceOnUse">
+                    <stop offset="0" stop-color="red"/>
+                    <stop offset="1" stop-color="blue"/>
+                </linearGradient>
+                <radialGradient id="rad" cx="50" cy="50" r="20" fx="45" fr="5"
+                        gradientUnits="userSpaceOnUse">
+                    <stop offset="0" stop-color="white"/>
+                    <stop offset="1" stop-color="black"/>
+                </radialGradient>
+                <radialGradient id="bbox" href="#rad" fr="10%" gradientUnits="objectBoundingBox"
+                        cx="0.5" cy="0.5" r="0.5"/>
+                <pattern id="dots" width="4" height="4" patternUnits="userSpaceOnUse">
+                    <circle cx="2" cy="2" r="1"/>
+                </pattern>
+            </defs>
+            <rect id="solid" x="1" y="2" width="3" height="4" fill="#ff0000" fill-opacity="0.5"
+                stroke="blue" stroke-width="2" stroke-linejoin="round"/>
+            <rect id="linear" width="10" height="10" fill="url(#lin)"/>
+            <circle id="radial" cx="50" cy="50" r="20" fill="url(#rad)"/>
+            <circle id="bbox-radial" cx="50" cy="50" r="20" fill="url(#bbox)"/>
+            <circle cx="50" cy="50" r="10" fill="url(#bbox)"/>
+            <rect id="dotted" width="10" height="10" fill="url(#dots)"/>
+            <g id="outer" opacity="0.5">
+                <g id="inner" opacity="0.25" transform="translate(10 0)">
+                    <rect id="nested" width="10" height="10"/>
+                </g>
+            </g>
+            <text id="label" x="0" y="50">Hello</text>
+        </svg>"##;
+        let imported = import_svg(svg).unwrap();
+        assert_eq!(imported.size, Size::new(100.0, 100.0));
+        let symbols = &imported.display_list.symbols;
+        let by_id = |id: &str| {
+            symbols
+                .iter()
+                .position(|s| s.metadata.as_ref().and_then(|m| m.id.as_deref()) == Some(id))
+                .unwrap_or_else(|| panic!("no symbol {id}"))
+        };
+
+        let solid = &symbols[by_id("solid")];
+        assert_eq!(solid.transform, Affine::IDENTITY);
+        assert!(matches!(solid.shape, StaticShape::BezPath(_)));
+        assert_eq!(solid.shape.bounding_box(), Rect::new(1.0, 2.0, 4.0, 6.0));
+        let [PaintOp::Fill { brush: fill, .. }, PaintOp::Stroke {
+            style,
+            brush: stroke,
+            alignment: StrokeAlignment::Center,
+        }] = &solid.paint_ops[..]
+        else {
+            panic!("{:?}", solid.paint_ops)
+        };
+        assert_eq!(*fill, Brush::Solid(Color::rgba8(255, 0, 0, 128)));
+        assert_eq!(*stroke, Brush::Solid(Color::rgb8(0, 0, 255)));
+        assert_eq!((style.width, style.join), (2.0, Join::Round));
+
+        let gradient = |id: &str| match &symbols[by_id(id)].paint_ops[..] {
+            [PaintOp::Fill {
+                brush: Brush::Gradient(gradient),
+                ..
+            }] => gradient.clone(),
+            ops => panic!("{ops:?}"),
+        };
+        let linear = gradient("linear");
+        assert_eq!(linear.stops.len(), 2);
+        assert!(matches!(
+            linear.kind,
+            GradientKind::Linear { start, end } if start.x == 0.0 && end.x == 10.0
+        ));
+        assert!(matches!(
+            gradient("radial").kind,
+            GradientKind::Radial { start_center, start_radius: 5.0, end_center, end_radius: 20.0 }
+                if start_center.x == 45.0 && end_center.x == 50.0
+        ));
+        // Gradients in bounding box units are renamed by usvg, and found again by their circles.
+        assert!(matches!(
+            gradient("bbox-radial").kind,
+            GradientKind::Radial { start_radius, end_radius: 0.5, .. }
+                if (start_radius - 0.1).abs() < 1e-6
+        ));
+        let bbox = by_id("bbox-radial");
+        assert_eq!(
+            symbols[bbox].brush_transform,
+            Some(Affine::new([40.0, 0.0, 0.0, 40.0, 30.0, 30.0]))
+        );
+        assert!(matches!(
+            &symbols[bbox + 1].paint_ops[..],
+            [PaintOp::Fill { brush: Brush::Gradient(g), .. }]
+                if matches!(g.kind, GradientKind::Radial { start_radius, .. } if start_radius > 0.0)
+        ));
+
+        // Groups with opacity are layers, nested inside each other.
+        let (outer, inner) = (by_id("outer"), by_id("inner"));
+        assert!(outer < inner && inner < by_id("nested"));
+        let alpha = |i: usize| match symbols[i].paint_ops[..] {
+            [PaintOp::PushLayer { alpha, .. }] => alpha,
+            _ => panic!("{:?}", symbols[i].paint_ops),
+        };
+        assert_eq!((alpha(outer), alpha(inner)), (0.5, 0.25));
+        assert_eq!(symbols[inner].transform, Affine::translate((10.0, 0.0)));
+        let pops = symbols[by_id("nested") + 1..]
+            .iter()
+            .take_while(|s| s.paint_ops == [PaintOp::PopLayer])
+            .count();
+        assert_eq!(pops, 2);
+
+        assert_eq!(
+            imported.diagnostics,
+            [
+                Diagnostic {
+                    id: "label".into(),
+                    unsupported: Unsupported::Text
+                },
+                Diagnostic {
+                    id: "dotted".into(),
+                    unsupported: Unsupported::Pattern
+                },
+            ]
+        );
+        assert!(import_svg("<svg").is_err());
+    }
+
+    #[test]
+    fn svg_import_clip_paths() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <clipPath id="frame">
+                <path d="M0 0H100V100H0Z M25 25H75V75H25Z" clip-rule="evenodd"/>
+            </clipPath>
+            <clipPath id="pair">
+                <path d="M0 0H60V60H0Z"/>
+                <path d="M40 40V100H100V40Z"/>
+            </clipPath>
+            <g id="framed" clip-path="url(#frame)"><rect width="100" height="100"/></g>
+            <g id="paired" clip-path="url(#pair)"><rect width="100" height="100"/></g>
+        </svg>"##;
+        let imported = import_svg(svg).unwrap();
+        let clip = |id: &str| {
+            let symbol = imported
+                .display_list
+                .symbols
+                .iter()
+                .find(|s| s.metadata.as_ref().and_then(|m| m.id.as_deref()) == Some(id))
+                .unwrap();
+            assert!(matches!(symbol.paint_ops[..], [PaintOp::PushLayer { .. }]));
+            symbol.shape.clone()
+        };
+        // Layers clip with the nonzero rule, the even-odd hole has to stay a hole.
+        let framed = clip("framed");
+        assert_ne!(framed.winding((10.0, 10.0).into()), 0);
+        assert_eq!(framed.winding((50.0, 50.0).into()), 0);
+        // The two squares wind in opposite directions, but their overlap is still covered.
+        let paired = clip("paired");
+        for p in [(10.0, 10.0), (50.0, 50.0), (90.0, 90.0)] {
+            assert!(paired.winding(p.into()) > 0, "{p:?}");
+        }
+        assert_eq!(paired.winding((90.0, 10.0).into()), 0);
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn svg_import_quads_to_pdf() {
+        use crate::test_util::{uncompressed, written};
+        use crate::{Pdf, TileOptions, TiledPdf};
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="60" height="40">
+            <path d="M0 0Q30 30 60 0" fill="none" stroke="black"/>
+        </svg>"#;
+        let imported = import_svg(svg).unwrap();
+        let mut pdf = Pdf::with_options(imported.size, 0.1, uncompressed());
+        imported.display_list.replay(&mut pdf);
+        let out = written(pdf);
+        assert!(out.contains("0 0 m\n20 20 40 20 60 0 c"), "{out}");
+
+        let tile_options = TileOptions {
+            compression_level: 0,
+            ..TileOptions::new(TileOptions::A4)
+        };
+        let mut tiles = TiledPdf::new(imported.size, 0.1, tile_options);
+        imported.display_list.replay(&mut tiles);
+        let mut out = Vec::new();
+        tiles.write(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("20 20 40 20 60 0 c"));
+    }
+}
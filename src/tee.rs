@@ -0,0 +1,301 @@
+use crate::metadata::Metadata;
+use crate::shape::StaticShape;
+use crate::whisperer::PaintOpRef;
+use crate::SceneWhisperer;
+use kurbo::{Affine, Shape};
+
+/// Tolerance used to convert shapes which aren't exposed by `Shape` into a `BezPath`.
+const TOLERANCE: f64 = 1e-3;
+
+/// An object safe companion to `SceneWhisperer`, implemented for every whisperer.
+///
+/// This allows different backends to be kept together as `Box<dyn DynSceneWhisperer>`,
+/// a `Vec` of which forwards every op to each of them in turn.
+///
+/// ```
+/// # use selvage::{DisplayList, DynSceneWhisperer, PaintOpRef, SceneWhisperer};
+/// # use kurbo::{Affine, Circle};
+/// let (mut a, mut b) = (DisplayList::new(), DisplayList::new());
+/// let mut tee: Vec<Box<dyn DynSceneWhisperer>> = vec![Box::new(&mut a), Box::new(&mut b)];
+/// let fill = PaintOpRef::Fill {
+///     style: peniko::Fill::NonZero,
+///     brush: (&peniko::Color::BLACK).into(),
+/// };
+/// tee.apply_paint_op(fill, Affine::IDENTITY, None, &Circle::new((0.0, 0.0), 1.0));
+/// drop(tee);
+/// assert_eq!((a.symbols.len(), b.symbols.len()), (1, 1));
+/// ```
+pub trait DynSceneWhisperer {
+    fn apply_paint_ops_dyn(
+        &mut self,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &StaticShape,
+    );
+
+    fn apply_paint_ops_with_metadata_dyn(
+        &mut self,
+        metadata: &Metadata,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &StaticShape,
+    );
+}
+
+impl<T: SceneWhisperer> DynSceneWhisperer for T {
+    fn apply_paint_ops_dyn(
+        &mut self,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &StaticShape,
+    ) {
+        self.apply_paint_ops(ops.iter().copied(), transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops_with_metadata_dyn(
+        &mut self,
+        metadata: &Metadata,
+        ops: &[PaintOpRef<'_, '_>],
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &StaticShape,
+    ) {
+        self.apply_paint_ops_with_metadata(
+            metadata,
+            ops.iter().copied(),
+            transform,
+            brush_transform,
+            shape,
+        );
+    }
+}
+
+/// Forwards ops to a `DynSceneWhisperer`, with `metadata` if there is any.
+fn apply_dyn(
+    whisperer: &mut (impl DynSceneWhisperer + ?Sized),
+    metadata: Option<&Metadata>,
+    ops: &[PaintOpRef<'_, '_>],
+    transform: Affine,
+    brush_transform: Option<Affine>,
+    shape: &StaticShape,
+) {
+    match metadata {
+        Some(metadata) => whisperer.apply_paint_ops_with_metadata_dyn(
+            metadata,
+            ops,
+            transform,
+            brush_transform,
+            shape,
+        ),
+        None => whisperer.apply_paint_ops_dyn(ops, transform, brush_transform, shape),
+    }
+}
+
+impl SceneWhisperer for Box<dyn DynSceneWhisperer + '_> {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.apply_paint_ops([op], transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        let ops: Vec<_> = ops.into_iter().collect();
+        let shape = StaticShape::from_shape(shape, TOLERANCE);
+        self.as_mut()
+            .apply_paint_ops_dyn(&ops, transform, brush_transform, &shape);
+    }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        let ops: Vec<_> = ops.into_iter().collect();
+        let shape = StaticShape::from_shape(shape, TOLERANCE);
+        self.as_mut().apply_paint_ops_with_metadata_dyn(
+            metadata,
+            &ops,
+            transform,
+            brush_transform,
+            &shape,
+        );
+    }
+}
+
+impl SceneWhisperer for Vec<Box<dyn DynSceneWhisperer + '_>> {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.apply_paint_ops([op], transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        tee(self, None, ops, transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        tee(self, Some(metadata), ops, transform, brush_transform, shape);
+    }
+}
+
+/// Forwards the ops to each whisperer in order, converting the shape only once.
+fn tee<'a, 'b>(
+    whisperers: &mut [Box<dyn DynSceneWhisperer + '_>],
+    metadata: Option<&Metadata>,
+    ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    transform: Affine,
+    brush_transform: Option<Affine>,
+    shape: &impl Shape,
+) {
+    let ops: Vec<_> = ops.into_iter().collect();
+    let shape = StaticShape::from_shape(shape, TOLERANCE);
+    for whisperer in whisperers {
+        apply_dyn(
+            whisperer.as_mut(),
+            metadata,
+            &ops,
+            transform,
+            brush_transform,
+            &shape,
+        );
+    }
+}
+
+/// Implements `SceneWhisperer` for tuples, forwarding each op to every element in order.
+macro_rules! tee_tuple {
+    ($($name:ident: $index:tt),+) => {
+        impl<$($name: SceneWhisperer),+> SceneWhisperer for ($($name,)+) {
+            fn apply_paint_op(
+                &mut self,
+                op: PaintOpRef<'_, '_>,
+                transform: Affine,
+                brush_transform: Option<Affine>,
+                shape: &impl Shape,
+            ) {
+                $(self.$index.apply_paint_op(op, transform, brush_transform, shape);)+
+            }
+
+            fn apply_paint_ops<'a, 'b, I>(
+                &mut self,
+                ops: I,
+                transform: Affine,
+                brush_transform: Option<Affine>,
+                shape: &impl Shape,
+            ) where
+                I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+            {
+                let ops: Vec<_> = ops.into_iter().collect();
+                $(self.$index.apply_paint_ops(ops.iter().copied(), transform, brush_transform, shape);)+
+            }
+
+            fn apply_paint_ops_with_metadata<'a, 'b, I>(
+                &mut self,
+                metadata: &Metadata,
+                ops: I,
+                transform: Affine,
+                brush_transform: Option<Affine>,
+                shape: &impl Shape,
+            ) where
+                I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+            {
+                let ops: Vec<_> = ops.into_iter().collect();
+                $(self.$index.apply_paint_ops_with_metadata(metadata, ops.iter().copied(), transform, brush_transform, shape);)+
+            }
+        }
+    };
+}
+
+tee_tuple!(A: 0, B: 1);
+tee_tuple!(A: 0, B: 1, C: 2);
+tee_tuple!(A: 0, B: 1, C: 2, D: 3);
+
+#[cfg(all(test, feature = "svg"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{push, stroke};
+    use crate::{DisplayList, StrokeAlignment, Svg};
+    use kurbo::{Circle, Line, Rect, Size, Stroke};
+    use peniko::{Color, Fill};
+
+    #[test]
+    fn tee_matches_members() {
+        let fill = PaintOpRef::Fill {
+            style: Fill::EvenOdd,
+            brush: (&Color::BLUE).into(),
+        };
+        let style = Stroke::new(1.5);
+        let stroke = stroke(&style, Color::RED, StrokeAlignment::Center);
+        let mut scene = DisplayList::new();
+        let circle = Circle::new((10.0, 10.0), 5.0);
+        scene.apply_paint_ops([push(0.5), fill, stroke], Affine::IDENTITY, None, &circle);
+        let labeled = Metadata::new().with_id("line").with_title("A line");
+        let line = Line::new((0.0, 0.0), (20.0, 20.0));
+        scene.apply_paint_ops_with_metadata(&labeled, [stroke], Affine::scale(0.5), None, &line);
+        scene.apply_paint_op(PaintOpRef::PopLayer, Affine::IDENTITY, None, &Rect::ZERO);
+        let rect = Rect::new(2.0, 2.0, 8.0, 8.0);
+        scene.apply_paint_ops([fill, stroke], Affine::translate((5.0, 0.0)), None, &rect);
+
+        let size = Size::new(20.0, 20.0);
+        let (mut svg, mut list) = (Svg::new(size), DisplayList::new());
+        #[cfg(feature = "pdf")]
+        let mut pdf = crate::Pdf::new(size, 0.1);
+        let mut tee: Vec<Box<dyn DynSceneWhisperer>> =
+            vec![Box::new(&mut svg), Box::new(&mut list)];
+        #[cfg(feature = "pdf")]
+        tee.push(Box::new(&mut pdf));
+        scene.replay(&mut tee);
+        drop(tee);
+
+        let mut direct = Svg::new(size);
+        scene.replay(&mut direct);
+        assert_eq!(svg.display().to_string(), direct.display().to_string());
+        assert_eq!(list, scene);
+        #[cfg(feature = "pdf")]
+        {
+            let mut direct = crate::Pdf::new(size, 0.1);
+            scene.replay(&mut direct);
+            assert!(crate::test_util::written(pdf) == crate::test_util::written(direct));
+        }
+    }
+}
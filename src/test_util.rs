@@ -0,0 +1,87 @@
+//! Fixtures shared by the tests of the modules.
+// Which of them are used depends on the features enabled.
+#![allow(dead_code)]
+
+use crate::{PaintOp, PaintOpRef, StaticShape, StrokeAlignment, Symbol};
+#[cfg(feature = "pdf")]
+use crate::{Pdf, PdfOptions};
+use kurbo::{Affine, Stroke};
+use peniko::{BlendMode, BrushRef, Color, Fill};
+
+/// A nonzero fill with a solid color.
+pub fn fill(color: Color) -> PaintOpRef<'static, 'static> {
+    PaintOpRef::Fill {
+        style: Fill::NonZero,
+        brush: BrushRef::Solid(color),
+    }
+}
+
+/// An owned [`fill`].
+pub fn fill_op(color: Color) -> PaintOp {
+    PaintOp::Fill {
+        style: Fill::NonZero,
+        brush: color.into(),
+    }
+}
+
+/// A stroke with a solid color.
+pub fn stroke(style: &Stroke, color: Color, alignment: StrokeAlignment) -> PaintOpRef<'_, 'static> {
+    PaintOpRef::Stroke {
+        style,
+        brush: BrushRef::Solid(color),
+        alignment,
+    }
+}
+
+/// An owned [`stroke`] of `width`.
+pub fn stroke_op(width: f64, color: Color, alignment: StrokeAlignment) -> PaintOp {
+    PaintOp::Stroke {
+        style: Stroke::new(width),
+        brush: color.into(),
+        alignment,
+    }
+}
+
+/// A layer with the default blend mode.
+pub fn push(alpha: f32) -> PaintOpRef<'static, 'static> {
+    PaintOpRef::PushLayer {
+        blend: BlendMode::default(),
+        alpha,
+    }
+}
+
+/// A symbol painting `paint_ops` on `shape`, without metadata.
+pub fn symbol(shape: impl Into<StaticShape>, paint_ops: Vec<PaintOp>, transform: Affine) -> Symbol {
+    Symbol {
+        shape: shape.into(),
+        paint_ops,
+        transform,
+        brush_transform: None,
+        metadata: None,
+    }
+}
+
+/// Asserts that `out` contains every one of `expected`.
+#[track_caller]
+pub fn assert_contains(out: &str, expected: &[&str]) {
+    for expected in expected {
+        assert!(out.contains(expected), "{expected} in {out}");
+    }
+}
+
+/// Options for documents whose content streams can be searched.
+#[cfg(feature = "pdf")]
+pub fn uncompressed() -> PdfOptions {
+    PdfOptions {
+        compression_level: 0,
+        ..PdfOptions::default()
+    }
+}
+
+/// The document written by `pdf`, as text.
+#[cfg(feature = "pdf")]
+pub fn written(pdf: Pdf) -> String {
+    let mut out = Vec::new();
+    pdf.write(&mut out).unwrap();
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,363 @@
+use crate::metadata::Metadata;
+use crate::offset::StrokeAlignment;
+use crate::shape::StaticShape;
+use crate::whisperer::{apply_paint_ops_with, PaintOpRef};
+use crate::SceneWhisperer;
+use kurbo::{Affine, BezPath, Circle, Line, PathEl, Point, Shape, Stroke};
+use peniko::{Color, Fill};
+use std::{fmt, io};
+
+/// Tolerance used to convert shapes which aren't exposed by `Shape` into a `BezPath`.
+const TOLERANCE: f64 = 1e-3;
+
+/// Debug geometry drawn on the inner whisperer after each fill and stroke.
+///
+/// Everything is drawn untransformed, so `width` and `point_radius` are in the same units
+/// as the output, and ops inside of layers have their overlay clipped by the layer.
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    /// The color of the bounding box of the transformed shape, if it should be drawn.
+    pub bounds: Option<Color>,
+    /// The color of the points of the transformed path, and the lines from
+    /// on curve points to their control points, if they should be drawn.
+    pub control_points: Option<Color>,
+    /// The colors of the x and y axes of the transform, if they should be drawn.
+    pub axes: Option<(Color, Color)>,
+    /// The length of the drawn axes.
+    pub axis_length: f64,
+    /// The width of the debug lines.
+    pub width: f64,
+    pub point_radius: f64,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            bounds: Some(Color::MAGENTA),
+            control_points: Some(Color::DARK_CYAN),
+            axes: Some((Color::RED, Color::GREEN)),
+            axis_length: 10.0,
+            width: 0.5,
+            point_radius: 1.0,
+        }
+    }
+}
+
+enum Sink<'a> {
+    Writer(&'a mut dyn io::Write),
+    #[cfg(feature = "log")]
+    Log(log::Level),
+}
+
+/// Logs every op passed through to an inner whisperer, optionally drawing debug geometry.
+///
+/// Each op is written as a line with its index, the op, the kind and bounding box of the
+/// shape, the transform and the brush, followed by the id of its metadata if it has one.
+pub struct Trace<'a, T: SceneWhisperer> {
+    whisperer: &'a mut T,
+    sink: Sink<'a>,
+    overlay: Option<Overlay>,
+    count: usize,
+    error: Option<io::Error>,
+}
+
+fn shape_kind(shape: &StaticShape) -> &'static str {
+    match shape {
+        StaticShape::PathSeg(_) => "PathSeg",
+        StaticShape::Arc(_) => "Arc",
+        StaticShape::BezPath(_) => "BezPath",
+        StaticShape::Circle(_) => "Circle",
+        StaticShape::CircleSegment(_) => "CircleSegment",
+        StaticShape::CubicBez(_) => "CubicBez",
+        StaticShape::Ellipse(_) => "Ellipse",
+        StaticShape::Line(_) => "Line",
+        StaticShape::QuadBez(_) => "QuadBez",
+        StaticShape::Rect(_) => "Rect",
+        StaticShape::RoundedRect(_) => "RoundedRect",
+    }
+}
+
+struct DisplayOp<'a, 'b, 'c>(&'c PaintOpRef<'a, 'b>);
+
+impl fmt::Display for DisplayOp<'_, '_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            PaintOpRef::Fill { style, brush } => write!(f, "Fill({style:?}) brush={brush:?}"),
+            PaintOpRef::Stroke {
+                style,
+                brush,
+                alignment,
+            } => {
+                write!(f, "Stroke(width={}, join={:?}", style.width, style.join)?;
+                if style.join == kurbo::Join::Miter {
+                    write!(f, ", miter_limit={}", style.miter_limit)?;
+                }
+                if *alignment != StrokeAlignment::Center {
+                    write!(f, ", {alignment:?}")?;
+                }
+                write!(f, ") brush={brush:?}")
+            }
+            PaintOpRef::PushLayer { blend, alpha } => {
+                write!(
+                    f,
+                    "PushLayer({:?}, {:?}, alpha={alpha})",
+                    blend.mix, blend.compose
+                )
+            }
+            PaintOpRef::PopLayer => write!(f, "PopLayer"),
+        }
+    }
+}
+
+impl<'w, T: SceneWhisperer> Trace<'w, T> {
+    /// Writes a line for every op to `writer`.
+    pub fn to_writer(whisperer: &'w mut T, writer: &'w mut dyn io::Write) -> Self {
+        Self::new(whisperer, Sink::Writer(writer))
+    }
+
+    /// Logs a record for every op at `level`, with the target `selvage::trace`.
+    #[cfg(feature = "log")]
+    pub fn to_log(whisperer: &'w mut T, level: log::Level) -> Self {
+        Self::new(whisperer, Sink::Log(level))
+    }
+
+    fn new(whisperer: &'w mut T, sink: Sink<'w>) -> Self {
+        Self {
+            whisperer,
+            sink,
+            overlay: None,
+            count: 0,
+            error: None,
+        }
+    }
+
+    /// Draws `overlay` on the inner whisperer after each fill and stroke.
+    pub fn with_overlay(mut self, overlay: Overlay) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Returns the first error writing the log, if there was one.
+    pub fn finish(self) -> io::Result<()> {
+        self.error.map_or(Ok(()), Err)
+    }
+
+    fn trace(
+        &mut self,
+        op: &PaintOpRef<'_, '_>,
+        metadata: Option<&Metadata>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &StaticShape,
+    ) {
+        let index = self.count;
+        self.count += 1;
+        let mut line = format!(
+            "{index}: {} shape={} bbox={:?} transform={:?} brush_transform={:?}",
+            DisplayOp(op),
+            shape_kind(shape),
+            shape.bounding_box(),
+            transform.as_coeffs(),
+            brush_transform.map(|t| t.as_coeffs()),
+        );
+        if let Some(id) = metadata.and_then(|metadata| metadata.id.as_ref()) {
+            line += &format!(" id={id:?}");
+        }
+        match &mut self.sink {
+            Sink::Writer(writer) => {
+                if self.error.is_none() {
+                    self.error = writeln!(writer, "{line}").err();
+                }
+            }
+            #[cfg(feature = "log")]
+            Sink::Log(level) => log::log!(target: "selvage::trace", *level, "{line}"),
+        }
+    }
+
+    fn trace_ops<'a, 'b>(
+        &mut self,
+        metadata: Option<&Metadata>,
+        ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let ops: Vec<_> = ops.into_iter().collect();
+        let captured = StaticShape::from_shape(shape, TOLERANCE);
+        for op in &ops {
+            self.trace(op, metadata, transform, brush_transform, &captured);
+        }
+        apply_paint_ops_with(
+            self.whisperer,
+            metadata,
+            ops.iter().copied(),
+            transform,
+            brush_transform,
+            shape,
+        );
+        if ops
+            .iter()
+            .any(|op| matches!(op, PaintOpRef::Fill { .. } | PaintOpRef::Stroke { .. }))
+        {
+            self.draw_overlay(transform, &captured);
+        }
+    }
+
+    fn draw_overlay(&mut self, transform: Affine, shape: &StaticShape) {
+        let Some(overlay) = &self.overlay else {
+            return;
+        };
+        let style = Stroke::new(overlay.width);
+        let stroke = |color: &Color| PaintOpRef::Stroke {
+            style: &style,
+            brush: color.into(),
+            alignment: StrokeAlignment::Center,
+        };
+        let path = transform * shape.to_path(TOLERANCE);
+        if let Some(color) = &overlay.bounds {
+            self.whisperer.apply_paint_op(
+                stroke(color),
+                Affine::IDENTITY,
+                None,
+                &path.bounding_box(),
+            );
+        }
+        if let Some(color) = &overlay.control_points {
+            let mut handles = BezPath::new();
+            let mut dots = BezPath::new();
+            let mut last = Point::ZERO;
+            let mut dot = |p: Point| {
+                dots.extend(Circle::new(p, overlay.point_radius).path_elements(TOLERANCE))
+            };
+            for el in path.elements() {
+                match *el {
+                    PathEl::MoveTo(p) | PathEl::LineTo(p) => dot(p),
+                    PathEl::QuadTo(p1, p2) => {
+                        handles.extend([
+                            PathEl::MoveTo(last),
+                            PathEl::LineTo(p1),
+                            PathEl::LineTo(p2),
+                        ]);
+                        dot(p1);
+                        dot(p2);
+                    }
+                    PathEl::CurveTo(p1, p2, p3) => {
+                        handles.extend([PathEl::MoveTo(last), PathEl::LineTo(p1)]);
+                        handles.extend([PathEl::MoveTo(p2), PathEl::LineTo(p3)]);
+                        dot(p1);
+                        dot(p2);
+                        dot(p3);
+                    }
+                    PathEl::ClosePath => (),
+                }
+                last = el.end_point().unwrap_or(last);
+            }
+            if !handles.elements().is_empty() {
+                self.whisperer
+                    .apply_paint_op(stroke(color), Affine::IDENTITY, None, &handles);
+            }
+            let fill = PaintOpRef::Fill {
+                style: Fill::NonZero,
+                brush: color.into(),
+            };
+            self.whisperer
+                .apply_paint_op(fill, Affine::IDENTITY, None, &dots);
+        }
+        if let Some((x, y)) = &overlay.axes {
+            let origin = transform * Point::ZERO;
+            for (color, axis) in [(x, Point::new(1.0, 0.0)), (y, Point::new(0.0, 1.0))] {
+                let v = transform * axis - origin;
+                if v.hypot() == 0.0 {
+                    continue;
+                }
+                let end = origin + v.normalize() * overlay.axis_length;
+                self.whisperer.apply_paint_op(
+                    stroke(color),
+                    Affine::IDENTITY,
+                    None,
+                    &Line::new(origin, end),
+                );
+            }
+        }
+    }
+}
+
+impl<'w, T: SceneWhisperer> SceneWhisperer for Trace<'w, T> {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        self.apply_paint_ops([op], transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.trace_ops(None, ops, transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        self.trace_ops(Some(metadata), ops, transform, brush_transform, shape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fill;
+    use crate::DisplayList;
+
+    #[test]
+    fn trace_logs_and_overlays() {
+        let mut list = DisplayList::new();
+        let mut log = Vec::new();
+        let mut trace = Trace::to_writer(&mut list, &mut log).with_overlay(Overlay::default());
+        let fill = fill(Color::BLACK);
+        let circle = Circle::new((0.0, 0.0), 1.0);
+        trace.apply_paint_op(fill, Affine::translate((5.0, 5.0)), None, &circle);
+        trace.finish().unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.starts_with("0: Fill(NonZero)"));
+        assert!(log.contains("shape=Circle"));
+        // The fill, then the bounds, handles, points and two axes.
+        assert_eq!(list.symbols.len(), 6);
+
+        // The inner whisperer gets the shape itself, flattened at its own tolerance.
+        #[cfg(feature = "svg")]
+        {
+            use crate::{Svg, SvgOptions};
+            use kurbo::{Ellipse, Size};
+            let options = SvgOptions {
+                tolerance: 1.0,
+                ..SvgOptions::default()
+            };
+            let ellipse = Ellipse::new((5.0, 5.0), (4.0, 2.0), 0.5);
+            let mut direct = Svg::with_options(Size::new(10.0, 10.0), options.clone());
+            direct.apply_paint_op(fill, Affine::IDENTITY, None, &ellipse);
+            let mut traced = Svg::with_options(Size::new(10.0, 10.0), options);
+            let mut sink = io::sink();
+            let mut trace = Trace::to_writer(&mut traced, &mut sink);
+            trace.apply_paint_op(fill, Affine::IDENTITY, None, &ellipse);
+            trace.finish().unwrap();
+            assert_eq!(traced.display().to_string(), direct.display().to_string());
+        }
+    }
+}
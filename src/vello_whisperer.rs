@@ -1,6 +1,8 @@
 #![cfg(feature = "vello")]
+use crate::offset::StrokeAlignment;
 use crate::whisperer::*;
-use kurbo::{Affine, Shape};
+use kurbo::{Affine, Shape, Stroke};
+use peniko::{BlendMode, Color, Compose, Fill, Mix};
 use vello::Scene;
 
 impl SceneWhisperer for Scene {
@@ -15,12 +17,46 @@ impl SceneWhisperer for Scene {
             PaintOpRef::Fill { style, brush } => {
                 self.fill(style, transform, brush, brush_transform, shape)
             }
-            PaintOpRef::Stroke { style, brush } => {
-                self.stroke(style, transform, brush, brush_transform, shape)
-            }
+            PaintOpRef::Stroke {
+                style,
+                brush,
+                alignment,
+            } => match alignment {
+                StrokeAlignment::Center => {
+                    self.stroke(style, transform, brush, brush_transform, shape)
+                }
+                // Half of a stroke twice as wide, clipped to the shape.
+                StrokeAlignment::Inside => {
+                    let wide = Stroke {
+                        width: style.width * 2.0,
+                        ..style.clone()
+                    };
+                    self.push_layer(Mix::Clip, 1.0, transform, shape);
+                    self.stroke(&wide, transform, brush, brush_transform, shape);
+                    self.pop_layer();
+                }
+                // Vello only clips with the nonzero rule, so the inside of a stroke twice as
+                // wide is knocked out by filling the shape in a layer composited `DestOut`.
+                StrokeAlignment::Outside => {
+                    let wide = Stroke {
+                        width: style.width * 2.0,
+                        ..style.clone()
+                    };
+                    let grow = style.width * style.miter_limit.max(1.0);
+                    let frame = shape.bounding_box().inflate(grow, grow);
+                    self.push_layer(Mix::Normal, 1.0, transform, &frame);
+                    self.stroke(&wide, transform, brush, brush_transform, shape);
+                    let knock_out = BlendMode::new(Mix::Normal, Compose::DestOut);
+                    self.push_layer(knock_out, 1.0, transform, shape);
+                    self.fill(Fill::NonZero, transform, Color::BLACK, None, shape);
+                    self.pop_layer();
+                    self.pop_layer();
+                }
+            },
             PaintOpRef::PushLayer { blend, alpha } => {
                 self.push_layer(blend, alpha, transform, shape)
             }
+            PaintOpRef::PopLayer => self.pop_layer(),
         }
     }
     fn apply_paint_ops<'a, 'b, I>(
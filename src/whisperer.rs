@@ -1,9 +1,13 @@
 #![allow(unused)]
+use crate::metadata::Metadata;
+use crate::offset::StrokeAlignment;
+use crate::print_color::PrintColor;
 use kurbo::{Affine, Point, Rect, Shape, Stroke};
-use peniko::{BlendMode, Brush, BrushRef, Fill};
+use peniko::{BlendMode, Brush, BrushRef, Color, Fill};
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 
+#[derive(Clone, Copy, Debug)]
 pub enum PaintOpRef<'a, 'b> {
     Fill {
         style: Fill,
@@ -12,18 +16,35 @@ pub enum PaintOpRef<'a, 'b> {
     Stroke {
         style: &'a Stroke,
         brush: BrushRef<'b>,
+        alignment: StrokeAlignment,
     },
+    /// Pushes a layer clipped to the shape, which is composited with `blend` and `alpha`
+    /// when the matching `PopLayer` is applied.
     PushLayer {
         blend: BlendMode,
         alpha: f32,
     },
+    /// Pops the most recently pushed layer, the transform and shape are ignored.
+    PopLayer,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PaintOp {
-    Fill { style: Fill, brush: Brush },
-    Stroke { style: Stroke, brush: Brush },
-    PushLayer { blend: BlendMode, alpha: f32 },
+    Fill {
+        style: Fill,
+        brush: Brush,
+    },
+    Stroke {
+        style: Stroke,
+        brush: Brush,
+        #[serde(default)]
+        alignment: StrokeAlignment,
+    },
+    PushLayer {
+        blend: BlendMode,
+        alpha: f32,
+    },
+    PopLayer,
 }
 
 impl<'a> From<&'a PaintOp> for PaintOpRef<'a, 'a> {
@@ -33,14 +54,42 @@ impl<'a> From<&'a PaintOp> for PaintOpRef<'a, 'a> {
                 style: *style,
                 brush: brush.into(),
             },
-            PaintOp::Stroke { style, brush } => PaintOpRef::Stroke {
+            PaintOp::Stroke {
+                style,
+                brush,
+                alignment,
+            } => PaintOpRef::Stroke {
                 style,
                 brush: brush.into(),
+                alignment: *alignment,
             },
             PaintOp::PushLayer { blend, alpha } => PaintOpRef::PushLayer {
                 blend: *blend,
                 alpha: *alpha,
             },
+            PaintOp::PopLayer => PaintOpRef::PopLayer,
+        }
+    }
+}
+
+impl From<PaintOpRef<'_, '_>> for PaintOp {
+    fn from(x: PaintOpRef<'_, '_>) -> PaintOp {
+        match x {
+            PaintOpRef::Fill { style, brush } => PaintOp::Fill {
+                style,
+                brush: brush.to_owned(),
+            },
+            PaintOpRef::Stroke {
+                style,
+                brush,
+                alignment,
+            } => PaintOp::Stroke {
+                style: style.clone(),
+                brush: brush.to_owned(),
+                alignment,
+            },
+            PaintOpRef::PushLayer { blend, alpha } => PaintOp::PushLayer { blend, alpha },
+            PaintOpRef::PopLayer => PaintOp::PopLayer,
         }
     }
 }
@@ -61,4 +110,104 @@ pub trait SceneWhisperer {
         shape: &impl Shape,
     ) where
         I: IntoIterator<Item = PaintOpRef<'a, 'b>>;
+
+    /// Applies `ops` on behalf of what `metadata` describes.
+    ///
+    /// The default ignores the metadata, adapters pass it on to their inner whisperer.
+    /// With layer ops, the metadata only applies to the layers pushed.
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        let _ = metadata;
+        self.apply_paint_ops(ops, transform, brush_transform, shape);
+    }
+}
+
+/// Applies `ops` with `metadata` if there is any.
+///
+/// Solid brushes are replaced with the sRGB approximations of the print colors of the
+/// metadata, keeping their alpha, so that backends without print colors paint what would
+/// be printed.
+pub(crate) fn apply_paint_ops_with<'a, 'b>(
+    whisperer: &mut impl SceneWhisperer,
+    metadata: Option<&Metadata>,
+    ops: impl IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    transform: Affine,
+    brush_transform: Option<Affine>,
+    shape: &impl Shape,
+) {
+    let Some(metadata) = metadata else {
+        whisperer.apply_paint_ops(ops, transform, brush_transform, shape);
+        return;
+    };
+    let fill = metadata.fill_color.as_ref().map(PrintColor::to_srgb);
+    let stroke = metadata.stroke_color.as_ref().map(PrintColor::to_srgb);
+    let solid = |brush: BrushRef<'b>, color: Option<Color>| match (brush, color) {
+        (BrushRef::Solid(original), Some(color)) => BrushRef::Solid(Color {
+            a: original.a,
+            ..color
+        }),
+        _ => brush,
+    };
+    let ops = ops.into_iter().map(|op| match op {
+        PaintOpRef::Fill { style, brush } => PaintOpRef::Fill {
+            style,
+            brush: solid(brush, fill),
+        },
+        PaintOpRef::Stroke {
+            style,
+            brush,
+            alignment,
+        } => PaintOpRef::Stroke {
+            style,
+            brush: solid(brush, stroke),
+            alignment,
+        },
+        op => op,
+    });
+    whisperer.apply_paint_ops_with_metadata(metadata, ops, transform, brush_transform, shape);
+}
+
+impl<T: SceneWhisperer> SceneWhisperer for &mut T {
+    fn apply_paint_op(
+        &mut self,
+        op: PaintOpRef<'_, '_>,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        (**self).apply_paint_op(op, transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops<'a, 'b, I>(
+        &mut self,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        (**self).apply_paint_ops(ops, transform, brush_transform, shape);
+    }
+
+    fn apply_paint_ops_with_metadata<'a, 'b, I>(
+        &mut self,
+        metadata: &Metadata,
+        ops: I,
+        transform: Affine,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) where
+        I: IntoIterator<Item = PaintOpRef<'a, 'b>>,
+    {
+        (**self).apply_paint_ops_with_metadata(metadata, ops, transform, brush_transform, shape);
+    }
 }